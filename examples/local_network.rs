@@ -0,0 +1,91 @@
+//! Serves a directory of html and media files through a small simulated network
+//! and writes every document it contains, like the client does in the real simulation.
+//!
+//! ```text
+//! cargo run --features simulation --example local_network -- <site directory> [output directory]
+//! ```
+
+use std::{env, path::PathBuf, process, time::Duration};
+
+use media_client::{
+    simulation::{ServerContent, SimulationBuilder},
+    MediaClientExtEvent,
+};
+use messages::client_commands::{MediaClientCommand, MediaClientEvent};
+
+const CLIENT: u8 = 1;
+const TEXT_SERVER: u8 = 21;
+const MEDIA_SERVER: u8 = 22;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let Some(site) = args.next().map(PathBuf::from) else {
+        eprintln!("usage: local_network <site directory> [output directory]");
+        process::exit(1);
+    };
+    let output = args
+        .next()
+        .map_or_else(|| env::temp_dir().join("media_client_demo"), PathBuf::from);
+    let (text, media) = match (
+        ServerContent::text_from_dir(&site),
+        ServerContent::media_from_dir(&site),
+    ) {
+        (Ok(text), Ok(media)) => (text, media),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("cannot read {}: {e}", site.display());
+            process::exit(1);
+        }
+    };
+
+    // CLIENT - 11 - {12, 13} - 14 - {TEXT_SERVER, MEDIA_SERVER}
+    let simulation = SimulationBuilder::new(CLIENT, output.clone())
+        .drone(11, 0.05)
+        .drone(12, 0.05)
+        .drone(13, 0.05)
+        .drone(14, 0.05)
+        .server(TEXT_SERVER, text)
+        .server(MEDIA_SERVER, media)
+        .link(CLIENT, 11)
+        .link(11, 12)
+        .link(11, 13)
+        .link(12, 14)
+        .link(13, 14)
+        .link(14, TEXT_SERVER)
+        .link(14, MEDIA_SERVER)
+        .start();
+    println!("flooding the network...");
+    let classified = simulation.wait_ext_event(TIMEOUT, |event| match event {
+        MediaClientExtEvent::ServerDirectoryChanged(servers) if servers.len() == 2 => Some(()),
+        _ => None,
+    });
+    if classified.is_none() {
+        eprintln!("the servers did not answer GetServerType");
+        process::exit(1);
+    }
+
+    simulation.send_command(MediaClientCommand::AskFilesList(TEXT_SERVER));
+    let Some(files) = simulation.wait_event(TIMEOUT, |event| match event {
+        MediaClientEvent::ReceveidFileList(TEXT_SERVER, _, files) => Some(files),
+        _ => None,
+    }) else {
+        eprintln!("no files list from the text server");
+        process::exit(1);
+    };
+    for file_id in files {
+        simulation.send_command(MediaClientCommand::AskForFile(TEXT_SERVER, file_id.clone()));
+        let written = simulation.wait_ext_event(TIMEOUT, |event| match event {
+            MediaClientExtEvent::DocumentUpdated(update) if update.complete => Some(update.path),
+            _ => None,
+        });
+        match written {
+            Some(path) => println!("{file_id} -> {}", path.display()),
+            None => println!("{file_id} -> not assembled, a media is missing"),
+        }
+    }
+    println!(
+        "{} fragments dropped, documents in {}",
+        simulation.dropped_fragments(),
+        output.display()
+    );
+}
@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
 pub mod media_client;
+#[cfg(feature = "simulation")]
+pub mod simulation;
 
 pub use media_client::*;
@@ -0,0 +1,101 @@
+use std::{
+    fmt::Debug,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(test)]
+mod test;
+
+/// Where the `MediaClient` reads the time and how it waits.
+///
+/// Every timeout, latency, timestamp, output name, age of the outputs and time of the trace
+/// goes through it, so that a `ManualClock` makes them reproducible.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+    fn system_time(&self) -> SystemTime;
+    fn sleep(&self, duration: Duration);
+
+    /// milliseconds since `UNIX_EPOCH`
+    fn unix_millis(&self) -> u64 {
+        since_epoch(self.system_time())
+            .as_millis()
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+    /// seconds since `UNIX_EPOCH`
+    fn unix_secs(&self) -> u64 {
+        since_epoch(self.system_time()).as_secs()
+    }
+}
+
+fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// The real time, used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Time that only moves with `advance` and `sleep`, which returns at once
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    epoch: SystemTime,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Clock showing `epoch` as `system_time` until it is advanced
+    #[must_use]
+    pub fn new(epoch: SystemTime) -> Self {
+        Self {
+            start: Instant::now(),
+            epoch,
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+    pub fn advance(&self, duration: Duration) {
+        if let Ok(mut elapsed) = self.elapsed.lock() {
+            *elapsed += duration;
+        }
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+            .lock()
+            .map(|elapsed| *elapsed)
+            .unwrap_or_default()
+    }
+}
+
+impl Default for ManualClock {
+    /// starts at `UNIX_EPOCH`
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH)
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+    fn system_time(&self) -> SystemTime {
+        self.epoch + self.elapsed()
+    }
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
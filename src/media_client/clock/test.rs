@@ -0,0 +1,14 @@
+use super::*;
+
+#[test]
+fn test_manual_clock_moves_only_when_asked() {
+    let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_000));
+    let start = clock.now();
+    assert_eq!(clock.now(), start);
+    assert_eq!(clock.unix_secs(), 1_000);
+
+    clock.sleep(Duration::from_secs(2));
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(clock.now() - start, Duration::from_millis(2_500));
+    assert_eq!(clock.unix_millis(), 1_002_500);
+}
@@ -0,0 +1,88 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use wg_2024::network::NodeId;
+
+use super::{
+    clock::{Clock, SystemClock},
+    renderer::OutputMode,
+    text_cache::TextCachePolicy,
+};
+
+/// Where the documents of each client are written inside `output_root`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubdirectoryScheme {
+    /// every client writes directly in `output_root`, the `RetentionLimits` only apply
+    /// to the documents written by the client since it started
+    #[default]
+    Shared,
+    /// each client writes in `output_root/client_{id}`
+    PerClient,
+}
+
+/// What is done with a document once it is written
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ViewerLaunch {
+    /// only write it
+    None,
+    /// open it with `webbrowser`
+    #[default]
+    SystemBrowser,
+    /// run `program` with `args` followed by the path of the html
+    Command { program: String, args: Vec<String> },
+}
+
+/// Limits on the documents kept in the output directory, checked after each one is written.
+/// `None` means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetentionLimits {
+    pub max_bundles: Option<usize>,
+    pub max_age: Option<Duration>,
+    /// total size of the documents, the caches are not counted
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MediaClientConfig {
+    /// `browser/` under the current directory by default
+    pub output_root: PathBuf,
+    pub subdirectory: SubdirectoryScheme,
+    pub viewer: ViewerLaunch,
+    pub retention: RetentionLimits,
+    pub output_mode: OutputMode,
+    /// write each document as soon as its text arrives, with placeholders for the media
+    pub progressive: bool,
+    /// when `AskForFile` is answered from the `TextCache`, the server is always asked by default
+    pub text_cache_policy: TextCachePolicy,
+    pub clock: Arc<dyn Clock>,
+    /// seed of the random choices, like `MediaServerStrategy::Random`, taken from the OS if `None`
+    pub seed: Option<u64>,
+    /// how often the `Metrics` are sent to the ext controller, never if `None`
+    pub metrics_interval: Option<Duration>,
+}
+
+impl Default for MediaClientConfig {
+    fn default() -> Self {
+        Self {
+            output_root: std::env::current_dir().unwrap_or_default().join("browser"),
+            subdirectory: SubdirectoryScheme::default(),
+            viewer: ViewerLaunch::default(),
+            retention: RetentionLimits::default(),
+            output_mode: OutputMode::default(),
+            progressive: false,
+            text_cache_policy: TextCachePolicy::default(),
+            clock: Arc::new(SystemClock),
+            seed: None,
+            metrics_interval: None,
+        }
+    }
+}
+
+impl MediaClientConfig {
+    /// Directory the client `id` writes its documents and its `TextCache` in
+    pub fn output_dir(&self, id: NodeId) -> PathBuf {
+        match self.subdirectory {
+            SubdirectoryScheme::Shared => self.output_root.clone(),
+            SubdirectoryScheme::PerClient => self.output_root.join(format!("client_{id}")),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use messages::high_level_messages::ServerType;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::{NodeId, SourceRoutingHeader};
+
+use super::{
+    faults::{FaultConfig, FaultCounts},
+    file_assembler::{AssemblerReport, SizeMismatchPolicy},
+    file_catalogue::CatalogueEntry,
+    media_cache::MediaCacheStats,
+    media_server_selector::{MediaServerSelectionError, MediaServerStrategy},
+    metrics::Metrics,
+    renderer::{DocumentUpdate, OutputMode},
+    text_cache::TextCachePolicy,
+    topology::TopologySnapshot,
+};
+
+/// Commands that are specific to the `MediaClient` and are not part of the
+/// shared `MediaClientCommand` set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MediaClientExtCommand {
+    /// Asks for the graph the client currently knows
+    GetTopology,
+    /// Asks for the route used to reach a node and the alternatives the client knows about
+    GetRoute(NodeId),
+    /// Changes how the media server for each `GetMedia` is chosen
+    SetMediaServerStrategy(MediaServerStrategy),
+    GetMediaCacheStats,
+    /// Changes when `AskForFile` is answered from the `TextCache`
+    SetTextCachePolicy(TextCachePolicy),
+    /// Displays `(source_id, file_id)` from the `TextCache` without asking the server
+    OpenCachedFile(NodeId, String),
+    /// Asks for the files of every known text server
+    GetCatalogue,
+    /// Changes what happens to text files whose content does not match their `size`
+    SetSizeMismatchPolicy(SizeMismatchPolicy),
+    /// Changes how the next completed documents are written to disk
+    SetOutputMode(OutputMode),
+    /// Removes every document written in the output directory, the caches are kept
+    PurgeOutput,
+    /// Turns on or off the progressive rendering of the next documents
+    SetProgressive(bool),
+    /// Replaces the faults injected in the packets of the client, answered with `FaultCounts`
+    SetFaults(FaultConfig),
+    GetFaultCounts,
+    /// Asks for the `Metrics` now, they are also sent every `metrics_interval` of the config
+    GetMetrics,
+}
+
+/// Answers to `MediaClientExtCommand`s and notifications specific to the `MediaClient`
+#[derive(Debug, Clone)]
+pub enum MediaClientExtEvent {
+    Topology(TopologySnapshot),
+    Route {
+        destination: NodeId,
+        /// `None` if the destination is unreachable
+        current: Option<SourceRoutingHeader>,
+        /// other known paths, shortest first, as `[client, .., destination]`
+        alternatives: Vec<Vec<NodeId>>,
+    },
+    /// Sent when the packets of a session start to travel through a different path
+    RouteChanged {
+        session_id: u64,
+        destination: NodeId,
+        previous: Vec<NodeId>,
+        current: Vec<NodeId>,
+    },
+    /// Every classified server, sent once the queries started by a flood are answered
+    ServerDirectoryChanged(Vec<(NodeId, ServerType)>),
+    /// The media could not be requested, it will be once a media server is known
+    MediaServerUnavailable {
+        media_id: String,
+        error: MediaServerSelectionError,
+    },
+    MediaCacheStats(MediaCacheStats),
+    CachedFileOpened {
+        source_id: NodeId,
+        file_id: String,
+        /// seconds since `UNIX_EPOCH`
+        fetched_at: u64,
+    },
+    CachedFileMissing {
+        source_id: NodeId,
+        file_id: String,
+    },
+    /// Sent for every `FilesList` received, compared with the previous one of the same server
+    FilesListChanged {
+        server_id: NodeId,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    Catalogue(Vec<CatalogueEntry>),
+    /// A text file arrived with a content that does not match its `size`
+    FileSizeMismatch {
+        source_id: NodeId,
+        file_id: String,
+        expected: usize,
+        actual: usize,
+        /// `GetFile` was sent again
+        refetching: bool,
+    },
+    AssemblerReport(AssemblerReport),
+    OutputPurged {
+        removed: usize,
+    },
+    /// A document was written, sent after every media in progressive mode
+    DocumentUpdated(DocumentUpdate),
+    FaultCounts(FaultCounts),
+    Metrics(Metrics),
+}
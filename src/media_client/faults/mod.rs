@@ -0,0 +1,121 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+use wg_2024::packet::{Packet, PacketType};
+
+#[cfg(test)]
+mod test;
+
+/// Misbehaviours of the client, used to test how the network copes with it.
+///
+/// The shares are between 0 and 1, the default injects nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct FaultConfig {
+    /// share of the outbound fragments that are never sent
+    pub drop_fragments: f32,
+    /// how long each `Ack` is held before being sent
+    pub ack_delay: Duration,
+    /// share of the outbound fragments sent with an altered payload
+    pub corrupt_fragments: f32,
+    /// share of the outbound packets sent twice
+    pub duplicate_packets: f32,
+    /// `FloodRequest`s are not answered
+    pub ignore_flood_requests: bool,
+}
+
+/// How many times each fault was injected since the client started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultCounts {
+    pub dropped_fragments: u64,
+    pub delayed_acks: u64,
+    pub corrupted_fragments: u64,
+    pub duplicated_packets: u64,
+    pub ignored_flood_requests: u64,
+}
+
+/// Applies the `FaultConfig` to the packets going through `send_packet` and `handle_packet`.
+///
+/// It is used through `&self` like the senders, so its state is kept in `RefCell`s.
+pub(super) struct FaultInjector {
+    config: FaultConfig,
+    rng: RefCell<StdRng>,
+    counts: RefCell<FaultCounts>,
+    /// `Ack`s and when they have to be sent, earliest first
+    delayed: RefCell<VecDeque<(Instant, Packet)>>,
+}
+
+impl FaultInjector {
+    pub(super) fn new(rng: StdRng) -> Self {
+        Self {
+            config: FaultConfig::default(),
+            rng: RefCell::new(rng),
+            counts: RefCell::new(FaultCounts::default()),
+            delayed: RefCell::new(VecDeque::new()),
+        }
+    }
+    pub(super) fn set_config(&mut self, config: FaultConfig) {
+        self.config = config;
+    }
+    pub(super) fn counts(&self) -> FaultCounts {
+        *self.counts.borrow()
+    }
+    /// # Returns
+    /// the packets to send now in place of `packet`, none if it is dropped or delayed
+    pub(super) fn outbound(&self, mut packet: Packet, now: Instant) -> Vec<Packet> {
+        let mut counts = self.counts.borrow_mut();
+        match &mut packet.pack_type {
+            PacketType::MsgFragment(fragment) => {
+                if self.happens(self.config.drop_fragments) {
+                    counts.dropped_fragments += 1;
+                    return Vec::new();
+                }
+                if self.happens(self.config.corrupt_fragments) {
+                    let mut rng = self.rng.borrow_mut();
+                    let index = rng.gen_range(0..usize::from(fragment.length).max(1));
+                    fragment.data[index] ^= rng.gen_range(1..=u8::MAX);
+                    counts.corrupted_fragments += 1;
+                }
+            }
+            PacketType::Ack(_) if !self.config.ack_delay.is_zero() => {
+                counts.delayed_acks += 1;
+                let due = now + self.config.ack_delay;
+                let mut delayed = self.delayed.borrow_mut();
+                let index = delayed.partition_point(|(at, _)| *at <= due);
+                delayed.insert(index, (due, packet));
+                return Vec::new();
+            }
+            _ => (),
+        }
+        if self.happens(self.config.duplicate_packets) {
+            counts.duplicated_packets += 1;
+            return vec![packet.clone(), packet];
+        }
+        vec![packet]
+    }
+    /// # Returns
+    /// `true` if the `FloodRequest` has to be answered
+    pub(super) fn answer_flood_request(&self) -> bool {
+        if self.config.ignore_flood_requests {
+            self.counts.borrow_mut().ignored_flood_requests += 1;
+        }
+        !self.config.ignore_flood_requests
+    }
+    /// When the first delayed `Ack` has to be sent
+    pub(super) fn next_due(&self) -> Option<Instant> {
+        self.delayed.borrow().front().map(|(due, _)| *due)
+    }
+    /// Removes the delayed `Ack`s that have to be sent at `now`
+    pub(super) fn take_due(&self, now: Instant) -> Vec<Packet> {
+        let mut delayed = self.delayed.borrow_mut();
+        let due = delayed.iter().take_while(|(at, _)| *at <= now).count();
+        delayed.drain(..due).map(|(_, packet)| packet).collect()
+    }
+    fn happens(&self, share: f32) -> bool {
+        share > 0.0 && self.rng.borrow_mut().gen::<f32>() < share
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use crossbeam_channel::{unbounded, Receiver};
+use rand::SeedableRng;
+use wg_2024::{
+    network::SourceRoutingHeader,
+    packet::{FloodRequest, Fragment, NodeType, FRAGMENT_DSIZE},
+};
+
+use super::*;
+use crate::media_client::{
+    test_util::test_config, MediaClient, MediaClientExtCommand, MediaClientExtEvent,
+};
+
+fn fragment(index: u64) -> Packet {
+    let mut data = [0; FRAGMENT_DSIZE];
+    data[..5].copy_from_slice(b"hello");
+    Packet::new_fragment(
+        SourceRoutingHeader::with_first_hop(vec![1, 11, 21]),
+        7,
+        Fragment {
+            fragment_index: index,
+            total_n_fragments: 4,
+            length: 5,
+            data,
+        },
+    )
+}
+
+fn ack(index: u64) -> Packet {
+    Packet::new_ack(
+        SourceRoutingHeader::with_first_hop(vec![1, 11, 21]),
+        7,
+        index,
+    )
+}
+
+fn injector(config: FaultConfig) -> FaultInjector {
+    let mut injector = FaultInjector::new(StdRng::seed_from_u64(3));
+    injector.set_config(config);
+    injector
+}
+
+#[test]
+fn test_default_config_injects_nothing() {
+    let injector = FaultInjector::new(StdRng::seed_from_u64(3));
+    let now = Instant::now();
+
+    assert_eq!(injector.outbound(fragment(0), now), vec![fragment(0)]);
+    assert_eq!(injector.outbound(ack(0), now), vec![ack(0)]);
+    assert!(injector.answer_flood_request());
+    assert_eq!(injector.counts(), FaultCounts::default());
+}
+
+#[test]
+fn test_fragments_are_dropped_corrupted_and_duplicated() {
+    let now = Instant::now();
+    let dropping = injector(FaultConfig {
+        drop_fragments: 1.0,
+        ..FaultConfig::default()
+    });
+    assert!(dropping.outbound(fragment(0), now).is_empty());
+    assert_eq!(dropping.outbound(ack(0), now), vec![ack(0)]);
+    assert_eq!(dropping.counts().dropped_fragments, 1);
+
+    let corrupting = injector(FaultConfig {
+        corrupt_fragments: 1.0,
+        duplicate_packets: 1.0,
+        ..FaultConfig::default()
+    });
+    let sent = corrupting.outbound(fragment(0), now);
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0], sent[1]);
+    let (PacketType::MsgFragment(original), PacketType::MsgFragment(corrupted)) =
+        (fragment(0).pack_type, &sent[0].pack_type)
+    else {
+        panic!("not a fragment");
+    };
+    assert_ne!(original.data[..5], corrupted.data[..5]);
+    assert_eq!(original.data[5..], corrupted.data[5..]);
+    assert_eq!(
+        corrupting.counts(),
+        FaultCounts {
+            corrupted_fragments: 1,
+            duplicated_packets: 1,
+            ..FaultCounts::default()
+        }
+    );
+}
+
+#[test]
+fn test_dropped_fragments_depend_only_on_the_seed() {
+    let config = FaultConfig {
+        drop_fragments: 0.5,
+        ..FaultConfig::default()
+    };
+    let now = Instant::now();
+    let sent = |injector: FaultInjector| {
+        (0..64)
+            .map(|index| injector.outbound(fragment(index), now).len())
+            .collect::<Vec<usize>>()
+    };
+
+    let first = sent(injector(config));
+    assert_eq!(first, sent(injector(config)));
+    assert!(first.contains(&0) && first.contains(&1));
+}
+
+#[test]
+fn test_acks_are_held_until_due() {
+    let injector = injector(FaultConfig {
+        ack_delay: Duration::from_millis(100),
+        ..FaultConfig::default()
+    });
+    let now = Instant::now();
+
+    assert!(injector.outbound(ack(0), now).is_empty());
+    assert!(injector
+        .outbound(ack(1), now + Duration::from_millis(50))
+        .is_empty());
+    assert_eq!(injector.next_due(), Some(now + Duration::from_millis(100)));
+    assert!(injector
+        .take_due(now + Duration::from_millis(99))
+        .is_empty());
+    assert_eq!(
+        injector.take_due(now + Duration::from_millis(100)),
+        vec![ack(0)]
+    );
+    assert_eq!(
+        injector.take_due(now + Duration::from_millis(150)),
+        vec![ack(1)]
+    );
+    assert_eq!(injector.next_due(), None);
+    assert_eq!(injector.counts().delayed_acks, 2);
+}
+
+fn client(
+    drone_send: crossbeam_channel::Sender<Packet>,
+) -> (MediaClient, Receiver<MediaClientExtEvent>) {
+    let (ext_send, ext_recv) = unbounded();
+    let client = MediaClient::new(
+        1,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(11, drone_send)]),
+        test_config("faults"),
+    )
+    .with_ext_controller(ext_send, unbounded().1);
+    (client, ext_recv)
+}
+
+#[test]
+fn test_client_ignores_flood_requests_and_reports_it() {
+    let (drone_send, drone_recv) = unbounded();
+    let (mut client, ext_recv) = client(drone_send);
+    let request = || Packet {
+        routing_header: SourceRoutingHeader::new(Vec::new(), 0),
+        session_id: 9,
+        pack_type: PacketType::FloodRequest(FloodRequest {
+            flood_id: 4,
+            initiator_id: 2,
+            path_trace: vec![(2, NodeType::Client), (11, NodeType::Drone)],
+        }),
+    };
+
+    client.handle_ext_command(MediaClientExtCommand::SetFaults(FaultConfig {
+        ignore_flood_requests: true,
+        ..FaultConfig::default()
+    }));
+    client.handle_packet(request());
+    assert!(drone_recv.try_recv().is_err());
+
+    client.handle_ext_command(MediaClientExtCommand::SetFaults(FaultConfig::default()));
+    client.handle_packet(request());
+    assert!(matches!(
+        drone_recv.try_recv().map(|packet| packet.pack_type),
+        Ok(PacketType::FloodResponse(_))
+    ));
+
+    let counts = ext_recv
+        .try_iter()
+        .filter_map(|event| match event {
+            MediaClientExtEvent::FaultCounts(counts) => Some(counts.ignored_flood_requests),
+            _ => None,
+        })
+        .collect::<Vec<u64>>();
+    assert_eq!(counts, vec![0, 1]);
+}
+
+#[test]
+fn test_client_sends_delayed_acks_once_due() {
+    let (drone_send, drone_recv) = unbounded();
+    let (mut client, _ext_recv) = client(drone_send);
+    client.handle_ext_command(MediaClientExtCommand::SetFaults(FaultConfig {
+        ack_delay: Duration::from_secs(1),
+        ..FaultConfig::default()
+    }));
+
+    client.send_packet(ack(0), None);
+    assert!(drone_recv.try_recv().is_err());
+    let due = client.faults.next_due().unwrap();
+    client.send_delayed_acks(due);
+    assert_eq!(drone_recv.try_recv().unwrap(), ack(0));
+    assert!(client.clock.now() < due);
+}
@@ -0,0 +1,523 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use base64::{engine::general_purpose, Engine};
+use html_parser::{Dom, Element, Node};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use wg_2024::network::NodeId;
+
+use super::{
+    html_rewrite::{local_media_names, rewrite_img_src},
+    manifest::{BundleManifest, FetchInfo, MediaManifest},
+    media_cache::hex_encode,
+    sanitize::{validate_name, NameRejection},
+};
+
+#[cfg(test)]
+mod test;
+
+/// `(source_id, file_id)`
+/// # Note
+/// media file have not `source_id`
+type FileKey = (Option<NodeId>, String);
+
+type MediaContent = String;
+
+type Sha256Digest = [u8; 32];
+
+/// Media received before any document references them that are kept, the oldest are dropped first
+pub const MAX_UNCLAIMED_MEDIA: usize = 16;
+
+#[derive(Debug)]
+pub enum AddedFileReturn {
+    CompleteFile {
+        source_id: NodeId,
+        file_id: String,
+        content: String,
+        /// `(media_id, content)`
+        media_content: HashMap<String, MediaContent>,
+        /// `(media_id, name of the media next to the html)`, the `src` in `content` already use them
+        local_names: HashMap<String, String>,
+        manifest: Box<BundleManifest>,
+    },
+    /// A document still waiting for some of its media, used by the progressive rendering
+    PartialFile {
+        source_id: NodeId,
+        file_id: String,
+        /// the `src` already use `local_names`
+        content: String,
+        /// `(media_id, content)` of the media received so far
+        media_content: HashMap<String, MediaContent>,
+        /// `(media_id, name of the media next to the html)` of every media
+        local_names: HashMap<String, String>,
+    },
+    RefToMedia(Vec<FileKey>),
+}
+
+/// The content of a text file does not match the `size` sent with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeMismatch {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+/// What to do with a text file whose content does not match its `size`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SizeMismatchPolicy {
+    /// display it anyway
+    Accept,
+    /// drop it
+    Discard,
+    /// ask the server again, then drop it
+    Refetch { max_attempts: u8 },
+}
+
+impl Default for SizeMismatchPolicy {
+    fn default() -> Self {
+        Self::Refetch { max_attempts: 2 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    File,
+    Media,
+}
+
+/// Something the `FileAssembler` found while assembling, to be forwarded to the controller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblerReport {
+    /// the file is dropped, or the media is not fetched
+    RejectedName {
+        source_id: NodeId,
+        name: String,
+        kind: NameKind,
+        reason: NameRejection,
+    },
+    /// the media was left out of the document
+    IntegrityMismatch {
+        source_id: NodeId,
+        file_id: String,
+        media_id: String,
+        /// hex sha256 advertised in the `integrity` attribute
+        expected: String,
+        /// `None` if the content is not valid base64
+        actual: Option<String>,
+    },
+}
+
+#[derive(Default)]
+pub struct FileAssembler {
+    files: HashMap<FileKey, FileType>,
+    /// `media_id` -> documents that did not receive it yet
+    waiting: HashMap<String, Vec<FileKey>>,
+    /// `media_id` -> number of documents referencing it, media nobody references any more are dropped
+    references: HashMap<String, usize>,
+    /// media nobody references yet, oldest first, at most `MAX_UNCLAIMED_MEDIA`
+    unclaimed: VecDeque<String>,
+    reports: Vec<AssemblerReport>,
+    /// also return a `PartialFile` for every document that is still waiting
+    partial_files: bool,
+}
+
+impl FileAssembler {
+    pub fn new() -> Self {
+        FileAssembler::default()
+    }
+    #[must_use]
+    pub fn with_partial_files(mut self, partial_files: bool) -> Self {
+        self.partial_files = partial_files;
+        self
+    }
+    pub fn set_partial_files(&mut self, partial_files: bool) {
+        self.partial_files = partial_files;
+    }
+    /// Compares the length in bytes of `content` with the advertised `size`
+    ///
+    /// # Errors
+    /// `SizeMismatch` if they differ
+    pub fn check_size(content: &str, size: usize) -> Result<(), SizeMismatch> {
+        if content.len() == size {
+            Ok(())
+        } else {
+            Err(SizeMismatch {
+                expected: size,
+                actual: content.len(),
+            })
+        }
+    }
+    /// # Returns
+    /// in this order, if there are:
+    /// - the `PartialFile` of the document, if `partial_files` is set
+    /// - `RefToMedia` with the media that were not received yet, each one once
+    /// - the `CompleteFile` of the document
+    pub fn add_textfile(
+        &mut self,
+        source_id: NodeId,
+        file_id: &str,
+        content: String,
+        size: usize,
+        fetch: FetchInfo,
+    ) -> Vec<AddedFileReturn> {
+        if let Err(reason) = validate_name(file_id) {
+            self.reports.push(AssemblerReport::RejectedName {
+                source_id,
+                name: file_id.to_owned(),
+                kind: NameKind::File,
+                reason,
+            });
+            return Vec::new();
+        }
+        let mut text_file = TextFile::new_textfile(content, size, fetch);
+        let mut seen = HashSet::new();
+        text_file.media_ref.retain(|(_, media_id)| {
+            if let Err(reason) = validate_name(media_id) {
+                self.reports.push(AssemblerReport::RejectedName {
+                    source_id,
+                    name: media_id.clone(),
+                    kind: NameKind::Media,
+                    reason,
+                });
+                return false;
+            }
+            seen.insert(media_id.clone())
+        });
+        let key = (Some(source_id), file_id.to_owned());
+        self.forget_document(&key);
+        let mut missing = Vec::new();
+        for media_key in &text_file.media_ref {
+            *self.references.entry(media_key.1.clone()).or_default() += 1;
+            self.unclaimed.retain(|media_id| media_id != &media_key.1);
+            if !self.contains_media_file(None, &media_key.1) {
+                self.waiting
+                    .entry(media_key.1.clone())
+                    .or_default()
+                    .push(key.clone());
+                missing.push(media_key.clone());
+            }
+        }
+        text_file.remaining = missing.len();
+        self.files
+            .insert(key.clone(), FileType::TextFile(text_file));
+        if missing.is_empty() {
+            return self.take_complete_file(&key).into_iter().collect();
+        }
+        let mut assembled = Vec::new();
+        if self.partial_files {
+            assembled.extend(self.partial_file(&key));
+        }
+        assembled.push(AddedFileReturn::RefToMedia(missing));
+        assembled
+    }
+    /// A media no document references yet is kept until one does, see `MAX_UNCLAIMED_MEDIA`
+    ///
+    /// # Returns
+    /// the `CompleteFile` of every document that was only waiting for this media,
+    /// then the `PartialFile` of the others waiting for it if `partial_files` is set
+    pub fn add_media_file(
+        &mut self,
+        file_id: &str,
+        content: String,
+        fetch: FetchInfo,
+    ) -> Vec<AddedFileReturn> {
+        if !self.references.contains_key(file_id) {
+            self.unclaimed.retain(|media_id| media_id != file_id);
+            self.unclaimed.push_back(file_id.to_owned());
+            if self.unclaimed.len() > MAX_UNCLAIMED_MEDIA {
+                if let Some(oldest) = self.unclaimed.pop_front() {
+                    self.files.remove(&(None, oldest));
+                }
+            }
+        }
+        let decoded = general_purpose::STANDARD.decode(&content).ok();
+        self.files.insert(
+            (None, file_id.to_owned()),
+            FileType::MediaFile {
+                content,
+                digest: decoded.as_deref().map(media_digest),
+                bytes: decoded.map(|decoded| decoded.len()),
+                fetch,
+            },
+        );
+        let mut completed = Vec::new();
+        let mut still_waiting = Vec::new();
+        for key in self.waiting.remove(file_id).unwrap_or_default() {
+            let Some(FileType::TextFile(text_file)) = self.files.get_mut(&key) else {
+                continue;
+            };
+            text_file.remaining = text_file.remaining.saturating_sub(1);
+            if text_file.remaining == 0 {
+                completed.push(key);
+            } else {
+                still_waiting.push(key);
+            }
+        }
+        let mut assembled = completed
+            .iter()
+            .filter_map(|key| self.take_complete_file(key))
+            .collect::<Vec<AddedFileReturn>>();
+        if self.partial_files {
+            assembled.extend(
+                still_waiting
+                    .iter()
+                    .filter_map(|key| self.partial_file(key)),
+            );
+        }
+        assembled
+    }
+    /// The document still waiting for some media, as a `PartialFile`
+    pub fn partial(&self, source_id: NodeId, file_id: &str) -> Option<AddedFileReturn> {
+        self.partial_file(&(Some(source_id), file_id.to_owned()))
+    }
+    /// The document `key` with the media received so far
+    fn partial_file(&self, key: &FileKey) -> Option<AddedFileReturn> {
+        let Some(FileType::TextFile(text_file)) = self.files.get(key) else {
+            return None;
+        };
+        let local_names = local_media_names(text_file.media_ref.iter().map(|(_, id)| id));
+        let media_content = text_file
+            .media_ref
+            .iter()
+            .filter_map(|media_key| match self.files.get(media_key) {
+                // a media not matching the integrity is reported once the document is complete
+                Some(FileType::MediaFile {
+                    content, digest, ..
+                }) if text_file.integrity_matches(&media_key.1, digest.as_ref()) => {
+                    Some((media_key.1.clone(), content.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let content = rewrite_img_src(&text_file.content, |src| local_names.get(src).cloned());
+        Some(AddedFileReturn::PartialFile {
+            source_id: key.0.unwrap_or_default(),
+            file_id: key.1.clone(),
+            content,
+            media_content,
+            local_names,
+        })
+    }
+    /// Takes the document `key` out of the assembler with a copy of its media
+    fn take_complete_file(&mut self, key: &FileKey) -> Option<AddedFileReturn> {
+        let Some(FileType::TextFile(text_file)) = self.files.remove(key) else {
+            return None;
+        };
+        let source_id = key.0.unwrap_or_default();
+        let file_id = key.1.as_str();
+        let mut media_content = HashMap::new();
+        let mut media_fetches = Vec::new();
+        for media_ref in &text_file.media_ref {
+            let Some(FileType::MediaFile {
+                content,
+                digest,
+                bytes,
+                fetch,
+            }) = self.files.get(media_ref)
+            else {
+                continue;
+            };
+            if let Some(expected) = text_file.integrity.get(&media_ref.1) {
+                if digest.as_ref() != Some(expected) {
+                    self.reports.push(AssemblerReport::IntegrityMismatch {
+                        source_id,
+                        file_id: file_id.to_owned(),
+                        media_id: media_ref.1.clone(),
+                        expected: hex_encode(expected),
+                        actual: digest.map(hex_encode),
+                    });
+                    continue;
+                }
+            }
+            media_fetches.push((media_ref.1.clone(), *digest, *bytes, fetch.clone()));
+            media_content.insert(media_ref.1.clone(), content.clone());
+        }
+        for media_ref in &text_file.media_ref {
+            self.release_media(&media_ref.1);
+        }
+        let local_names = local_media_names(media_content.keys());
+        let mut media = media_fetches
+            .into_iter()
+            .map(|(media_id, digest, bytes, fetch)| MediaManifest {
+                local_name: local_names[&media_id].clone(),
+                sha256: digest.as_ref().map(hex_encode),
+                media_id,
+                bytes,
+                fetch,
+            })
+            .collect::<Vec<MediaManifest>>();
+        media.sort_unstable_by(|a, b| a.media_id.cmp(&b.media_id));
+        let manifest = text_file.manifest(source_id, file_id, media);
+        let content = rewrite_img_src(&text_file.content, |src| local_names.get(src).cloned());
+        Some(AddedFileReturn::CompleteFile {
+            source_id,
+            file_id: file_id.to_owned(),
+            content,
+            media_content,
+            local_names,
+            manifest: Box::new(manifest),
+        })
+    }
+    /// Drops the document `key` if it is still waiting for some media
+    fn forget_document(&mut self, key: &FileKey) {
+        let Some(FileType::TextFile(text_file)) = self.files.remove(key) else {
+            return;
+        };
+        for (_, media_id) in &text_file.media_ref {
+            if let Some(waiting) = self.waiting.get_mut(media_id) {
+                waiting.retain(|waiting_key| waiting_key != key);
+                if waiting.is_empty() {
+                    self.waiting.remove(media_id);
+                }
+            }
+            self.release_media(media_id);
+        }
+    }
+    /// One document less references `media_id`, it is dropped when none does
+    fn release_media(&mut self, media_id: &str) {
+        let Some(count) = self.references.get_mut(media_id) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.references.remove(media_id);
+            self.files.remove(&(None, media_id.to_owned()));
+        }
+    }
+    /// # Returns
+    /// a document waiting for `media_id` whose `integrity` does not match `content`
+    pub fn integrity_mismatch(&self, media_id: &str, content: &str) -> Option<(NodeId, String)> {
+        let digest = general_purpose::STANDARD
+            .decode(content)
+            .ok()
+            .map(|decoded| media_digest(&decoded));
+        self.waiting.get(media_id)?.iter().find_map(|key| {
+            let Some(FileType::TextFile(text_file)) = self.files.get(key) else {
+                return None;
+            };
+            let expected = text_file.integrity.get(media_id)?;
+            (digest.as_ref() != Some(expected)).then(|| (key.0.unwrap_or_default(), key.1.clone()))
+        })
+    }
+    /// # Returns
+    /// what was found since the last call
+    pub fn take_reports(&mut self) -> Vec<AssemblerReport> {
+        std::mem::take(&mut self.reports)
+    }
+    fn contains_media_file(&self, source_id: Option<NodeId>, media_id: &str) -> bool {
+        self.files.contains_key(&(source_id, media_id.to_owned()))
+    }
+}
+
+enum FileType {
+    TextFile(TextFile),
+    MediaFile {
+        content: String,
+        /// `None` if `content` is not valid base64
+        digest: Option<Sha256Digest>,
+        /// size of the decoded content, `None` if `content` is not valid base64
+        bytes: Option<usize>,
+        fetch: FetchInfo,
+    },
+}
+
+struct TextFile {
+    content: String,
+    size: usize,
+    media_ref: Vec<FileKey>,
+    /// `(media_id, sha256)` advertised by the document
+    integrity: HashMap<String, Sha256Digest>,
+    fetch: FetchInfo,
+    /// media of `media_ref` not received yet
+    remaining: usize,
+}
+impl TextFile {
+    /// `false` if the document advertises an `integrity` for `media_id` that `digest` does not match
+    fn integrity_matches(&self, media_id: &str, digest: Option<&Sha256Digest>) -> bool {
+        self.integrity
+            .get(media_id)
+            .is_none_or(|expected| digest == Some(expected))
+    }
+    /// The document with the media it references, `remaining` is set once they are looked up
+    fn new_textfile(content: String, size: usize, fetch: FetchInfo) -> Self {
+        let media_ref = search_ref(&content).unwrap_or_default();
+        let integrity = search_integrity(&content);
+        Self {
+            content,
+            size,
+            media_ref,
+            integrity,
+            fetch,
+            remaining: 0,
+        }
+    }
+    fn manifest(
+        &self,
+        source_id: NodeId,
+        file_id: &str,
+        media: Vec<MediaManifest>,
+    ) -> BundleManifest {
+        BundleManifest {
+            source_id,
+            file_id: file_id.to_owned(),
+            advertised_size: self.size,
+            bytes: self.content.len(),
+            fetch: self.fetch.clone(),
+            completed_at: media
+                .iter()
+                .map(|media| media.fetch.received_at)
+                .fold(self.fetch.received_at, u64::max),
+            media,
+        }
+    }
+}
+
+///get `media_ref` from
+/// `<img src="media_id">`
+///
+/// # Return
+/// An optional vec of `(None, media_id)`, `None` if `file` cannot be parsed
+fn search_ref(file: &str) -> Option<Vec<FileKey>> {
+    let dom = Dom::parse(file).ok()?;
+    let media_ref = img_elements(&dom)
+        .filter_map(|element| Some((None, element.attributes.get("src")?.clone()?)))
+        .collect::<Vec<FileKey>>();
+    Some(media_ref)
+}
+
+/// get the sha256 advertised with
+/// `<img src="media_id" integrity="sha256-base64digest">`
+///
+/// # Return
+/// `(media_id, digest)`, media without a valid `sha256` integrity are left out
+fn search_integrity(file: &str) -> HashMap<String, Sha256Digest> {
+    let Ok(dom) = Dom::parse(file) else {
+        return HashMap::new();
+    };
+    img_elements(&dom)
+        .filter_map(|element| {
+            let media_id = element.attributes.get("src")?.clone()?;
+            let integrity = element.attributes.get("integrity")?.as_deref()?;
+            let digest = integrity
+                .split_whitespace()
+                .find_map(|hash| hash.strip_prefix("sha256-"))?;
+            let digest = general_purpose::STANDARD.decode(digest).ok()?;
+            Some((media_id, digest.try_into().ok()?))
+        })
+        .collect()
+}
+
+/// every `<img>` of the document, under any of its root nodes
+fn img_elements(dom: &Dom) -> impl Iterator<Item = &Element> {
+    dom.children
+        .iter()
+        .flat_map(|root| root.into_iter())
+        .filter_map(|node| match node {
+            Node::Element(element) if element.name.eq_ignore_ascii_case("img") => Some(element),
+            _ => None,
+        })
+}
+
+/// sha256 of the decoded media
+fn media_digest(content: &[u8]) -> Sha256Digest {
+    Sha256::digest(content).into()
+}
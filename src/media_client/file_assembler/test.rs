@@ -0,0 +1,392 @@
+use super::*;
+
+/// Keeps what the `FileAssembler` returns in memory instead of writing it to disk
+#[derive(Default)]
+struct MemorySink {
+    complete: Vec<AddedFileReturn>,
+    partial: Vec<AddedFileReturn>,
+    /// `media_id` of every `RefToMedia`, in order
+    requested: Vec<String>,
+}
+
+impl MemorySink {
+    fn collect(&mut self, assembled: Vec<AddedFileReturn>) {
+        for file in assembled {
+            match file {
+                AddedFileReturn::CompleteFile { .. } => self.complete.push(file),
+                AddedFileReturn::PartialFile { .. } => self.partial.push(file),
+                AddedFileReturn::RefToMedia(media_ref) => self
+                    .requested
+                    .extend(media_ref.into_iter().map(|(_, media_id)| media_id)),
+            }
+        }
+    }
+    fn complete_ids(&self) -> Vec<(NodeId, String)> {
+        let mut ids = self
+            .complete
+            .iter()
+            .filter_map(|file| match file {
+                AddedFileReturn::CompleteFile {
+                    source_id, file_id, ..
+                } => Some((*source_id, file_id.clone())),
+                _ => None,
+            })
+            .collect::<Vec<(NodeId, String)>>();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+fn add_text(assembler: &mut FileAssembler, sink: &mut MemorySink, file_id: &str, html: &str) {
+    sink.collect(assembler.add_textfile(
+        1,
+        file_id,
+        html.to_owned(),
+        html.len(),
+        FetchInfo::default(),
+    ));
+}
+
+fn add_media(assembler: &mut FileAssembler, sink: &mut MemorySink, media_id: &str) {
+    sink.collect(assembler.add_media_file(media_id, media_content(media_id), FetchInfo::default()));
+}
+
+/// base64 content that is different for every media
+fn media_content(media_id: &str) -> String {
+    general_purpose::STANDARD.encode(format!("content of {media_id}"))
+}
+
+#[test]
+fn test_document_without_media_is_complete_at_once() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "plain.html",
+        "<html><p>no media</p></html>",
+    );
+
+    assert!(sink.requested.is_empty());
+    assert_eq!(sink.complete_ids(), vec![(1, "plain.html".to_owned())]);
+    let AddedFileReturn::CompleteFile {
+        content,
+        media_content,
+        manifest,
+        ..
+    } = &sink.complete[0]
+    else {
+        unreachable!()
+    };
+    assert_eq!(content, "<html><p>no media</p></html>");
+    assert!(media_content.is_empty());
+    assert_eq!(manifest.advertised_size, content.len());
+}
+
+#[test]
+fn test_document_with_one_media() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "one.html",
+        "<html><img src=\"images/a.jpg\"></html>",
+    );
+
+    assert_eq!(sink.requested, vec!["images/a.jpg".to_owned()]);
+    assert!(sink.complete.is_empty());
+
+    add_media(&mut assembler, &mut sink, "images/a.jpg");
+    assert_eq!(sink.complete_ids(), vec![(1, "one.html".to_owned())]);
+    let AddedFileReturn::CompleteFile {
+        content,
+        media_content: received,
+        local_names,
+        manifest,
+        ..
+    } = &sink.complete[0]
+    else {
+        unreachable!()
+    };
+    assert_eq!(content, "<html><img src=\"a.jpg\"></html>");
+    assert_eq!(received["images/a.jpg"], media_content("images/a.jpg"));
+    assert_eq!(local_names["images/a.jpg"], "a.jpg");
+    assert_eq!(manifest.media.len(), 1);
+    assert_eq!(
+        manifest.media[0].bytes,
+        Some("content of images/a.jpg".len())
+    );
+}
+
+#[test]
+fn test_document_with_many_media_arriving_out_of_order() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    let html = "<html><body><img src=\"a.jpg\"><p><img src=\"b.png\"></p><img src=\"c.gif\"></body></html>";
+    add_text(&mut assembler, &mut sink, "many.html", html);
+    assert_eq!(sink.requested, vec!["a.jpg", "b.png", "c.gif"]);
+
+    add_media(&mut assembler, &mut sink, "c.gif");
+    add_media(&mut assembler, &mut sink, "a.jpg");
+    assert!(sink.complete.is_empty());
+
+    add_media(&mut assembler, &mut sink, "b.png");
+    assert_eq!(sink.complete_ids(), vec![(1, "many.html".to_owned())]);
+    let AddedFileReturn::CompleteFile { media_content, .. } = &sink.complete[0] else {
+        unreachable!()
+    };
+    assert_eq!(media_content.len(), 3);
+}
+
+#[test]
+fn test_duplicated_media_is_requested_once() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    let html = "<html><img src=\"logo.png\"><img src=\"logo.png\"></html>";
+    add_text(&mut assembler, &mut sink, "dup.html", html);
+    assert_eq!(sink.requested, vec!["logo.png"]);
+
+    add_media(&mut assembler, &mut sink, "logo.png");
+    let AddedFileReturn::CompleteFile { content, .. } = &sink.complete[0] else {
+        unreachable!()
+    };
+    assert_eq!(content, html);
+}
+
+#[test]
+fn test_document_with_a_missing_media_is_never_complete() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "missing.html",
+        "<html><img src=\"here.jpg\"><img src=\"lost.jpg\"></html>",
+    );
+    add_media(&mut assembler, &mut sink, "here.jpg");
+    add_media(&mut assembler, &mut sink, "unrelated.jpg");
+
+    assert!(sink.complete.is_empty());
+}
+
+#[test]
+fn test_media_arriving_before_its_text_is_kept() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    add_media(&mut assembler, &mut sink, "early.jpg");
+    assert!(sink.complete.is_empty());
+
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "late.html",
+        "<html><img src=\"early.jpg\"></html>",
+    );
+    assert!(sink.requested.is_empty());
+    assert_eq!(sink.complete_ids(), vec![(1, "late.html".to_owned())]);
+    assert!(assembler.files.is_empty());
+}
+
+#[test]
+fn test_oldest_unclaimed_media_are_dropped() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    for i in 0..=MAX_UNCLAIMED_MEDIA {
+        add_media(&mut assembler, &mut sink, &format!("{i}.jpg"));
+    }
+    assert_eq!(assembler.files.len(), MAX_UNCLAIMED_MEDIA);
+
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "late.html",
+        "<html><img src=\"0.jpg\"><img src=\"1.jpg\"></html>",
+    );
+    assert_eq!(sink.requested, vec!["0.jpg"]);
+}
+
+#[test]
+fn test_shared_media_completes_every_document_at_once() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "first.html",
+        "<html><img src=\"shared.jpg\"></html>",
+    );
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "second.html",
+        "<html><img src=\"shared.jpg\"><img src=\"own.jpg\"></html>",
+    );
+    add_media(&mut assembler, &mut sink, "own.jpg");
+    add_media(&mut assembler, &mut sink, "shared.jpg");
+
+    assert_eq!(
+        sink.complete_ids(),
+        vec![(1, "first.html".to_owned()), (1, "second.html".to_owned())]
+    );
+    assert!(assembler.files.is_empty());
+    assert!(assembler.waiting.is_empty());
+    assert!(assembler.references.is_empty());
+}
+
+#[test]
+fn test_media_already_received_completes_a_new_document() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "first.html",
+        "<html><img src=\"shared.jpg\"><img src=\"slow.jpg\"></html>",
+    );
+    add_media(&mut assembler, &mut sink, "shared.jpg");
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "second.html",
+        "<html><img src=\"shared.jpg\"></html>",
+    );
+
+    assert_eq!(sink.requested, vec!["shared.jpg", "slow.jpg"]);
+    assert_eq!(sink.complete_ids(), vec![(1, "second.html".to_owned())]);
+}
+
+#[test]
+fn test_partial_files_are_returned_while_media_arrive() {
+    let mut assembler = FileAssembler::new().with_partial_files(true);
+    let mut sink = MemorySink::default();
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "progressive.html",
+        "<html><img src=\"a.jpg\"><img src=\"b.jpg\"></html>",
+    );
+    add_media(&mut assembler, &mut sink, "a.jpg");
+    add_media(&mut assembler, &mut sink, "b.jpg");
+
+    let received = sink
+        .partial
+        .iter()
+        .map(|file| match file {
+            AddedFileReturn::PartialFile {
+                media_content,
+                local_names,
+                ..
+            } => (media_content.len(), local_names.len()),
+            _ => unreachable!(),
+        })
+        .collect::<Vec<(usize, usize)>>();
+    assert_eq!(received, vec![(0, 2), (1, 2)]);
+    assert_eq!(sink.complete.len(), 1);
+}
+
+#[test]
+fn test_media_not_matching_its_integrity_is_left_out() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    let wrong = general_purpose::STANDARD.encode([0u8; 32]);
+    let html = format!("<html><img src=\"a.jpg\" integrity=\"sha256-{wrong}\"></html>");
+    add_text(&mut assembler, &mut sink, "integrity.html", &html);
+    add_media(&mut assembler, &mut sink, "a.jpg");
+
+    let AddedFileReturn::CompleteFile { media_content, .. } = &sink.complete[0] else {
+        unreachable!()
+    };
+    assert!(media_content.is_empty());
+    assert!(matches!(
+        assembler.take_reports()[..],
+        [AssemblerReport::IntegrityMismatch { .. }]
+    ));
+}
+
+#[test]
+fn test_partial_file_leaves_out_media_not_matching_its_integrity() {
+    let mut assembler = FileAssembler::new().with_partial_files(true);
+    let mut sink = MemorySink::default();
+    let wrong = general_purpose::STANDARD.encode([0u8; 32]);
+    let html =
+        format!("<html><img src=\"a.jpg\" integrity=\"sha256-{wrong}\"><img src=\"b.jpg\"></html>");
+    add_text(&mut assembler, &mut sink, "integrity.html", &html);
+    add_media(&mut assembler, &mut sink, "a.jpg");
+
+    let AddedFileReturn::PartialFile { media_content, .. } = sink.partial.last().unwrap() else {
+        unreachable!()
+    };
+    assert!(media_content.is_empty());
+    assert_eq!(
+        assembler
+            .partial(1, "integrity.html")
+            .map(|file| match file {
+                AddedFileReturn::PartialFile { media_content, .. } => media_content.len(),
+                _ => unreachable!(),
+            }),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_unsafe_names_are_reported() {
+    let mut assembler = FileAssembler::new();
+    let mut sink = MemorySink::default();
+    add_text(&mut assembler, &mut sink, "../escape.html", "<html></html>");
+    add_text(
+        &mut assembler,
+        &mut sink,
+        "safe.html",
+        "<html><img src=\"/etc/passwd\"></html>",
+    );
+
+    assert!(sink.requested.is_empty());
+    assert_eq!(sink.complete_ids(), vec![(1, "safe.html".to_owned())]);
+    let kinds = assembler
+        .take_reports()
+        .into_iter()
+        .filter_map(|report| match report {
+            AssemblerReport::RejectedName { kind, .. } => Some(kind),
+            _ => None,
+        })
+        .collect::<Vec<NameKind>>();
+    assert_eq!(kinds, vec![NameKind::File, NameKind::Media]);
+}
+
+#[test]
+fn test_check_size() {
+    assert_eq!(FileAssembler::check_size("four", 4), Ok(()));
+    assert_eq!(
+        FileAssembler::check_size("four", 5),
+        Err(SizeMismatch {
+            expected: 5,
+            actual: 4
+        })
+    );
+}
+
+#[test]
+fn test_search_ref() {
+    let media_ids = |html: &str| {
+        search_ref(html).map(|media_ref| {
+            media_ref
+                .into_iter()
+                .map(|(_, media_id)| media_id)
+                .collect::<Vec<String>>()
+        })
+    };
+    assert_eq!(media_ids("<html><p>text</p></html>"), Some(vec![]));
+    assert_eq!(
+        media_ids("<html><body><div><img src=\"deep.jpg\"></div></body></html>"),
+        Some(vec!["deep.jpg".to_owned()])
+    );
+    assert_eq!(
+        media_ids("<p>first root</p><img src=\"second_root.jpg\">"),
+        Some(vec!["second_root.jpg".to_owned()])
+    );
+    assert_eq!(
+        media_ids("<html><img alt=\"no src\"><img src=\"a.jpg\"></html>"),
+        Some(vec!["a.jpg".to_owned()])
+    );
+}
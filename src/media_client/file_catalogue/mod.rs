@@ -0,0 +1,48 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use wg_2024::network::NodeId;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilesListDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogueEntry {
+    pub server_id: NodeId,
+    pub file_id: String,
+    /// there is a copy in the `TextCache`
+    pub cached: bool,
+}
+
+/// Last `FilesList` received from each server
+#[derive(Default)]
+pub struct FileCatalogue {
+    lists: BTreeMap<NodeId, BTreeSet<String>>,
+}
+
+impl FileCatalogue {
+    /// Replaces the list of `server_id`
+    ///
+    /// # Returns
+    /// what changed since the previous list, everything is added the first time
+    pub fn update(&mut self, server_id: NodeId, files: &[String]) -> FilesListDiff {
+        let files = files.iter().cloned().collect::<BTreeSet<String>>();
+        let previous = self.lists.insert(server_id, files).unwrap_or_default();
+        let current = &self.lists[&server_id];
+        FilesListDiff {
+            added: current.difference(&previous).cloned().collect(),
+            removed: previous.difference(current).cloned().collect(),
+        }
+    }
+    /// Every known file as `(server_id, file_id)`, sorted by server and file
+    pub fn files(&self) -> impl Iterator<Item = (NodeId, &str)> {
+        self.lists.iter().flat_map(|(&server_id, files)| {
+            files.iter().map(move |file| (server_id, file.as_str()))
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use super::*;
+
+fn files(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|&id| id.to_owned()).collect()
+}
+
+#[test]
+fn test_files_list_diff() {
+    let mut catalogue = FileCatalogue::default();
+
+    let first = catalogue.update(21, &files(&["b.html", "a.html", "a.html"]));
+    assert_eq!(first.added, files(&["a.html", "b.html"]));
+    assert!(first.removed.is_empty());
+
+    let unchanged = catalogue.update(21, &files(&["b.html", "a.html"]));
+    assert_eq!(unchanged, FilesListDiff::default());
+
+    let changed = catalogue.update(21, &files(&["a.html", "c.html"]));
+    assert_eq!(changed.added, files(&["c.html"]));
+    assert_eq!(changed.removed, files(&["b.html"]));
+
+    let emptied = catalogue.update(21, &[]);
+    assert!(emptied.added.is_empty());
+    assert_eq!(emptied.removed, files(&["a.html", "c.html"]));
+}
+
+#[test]
+fn test_files_of_every_server() {
+    let mut catalogue = FileCatalogue::default();
+    catalogue.update(22, &files(&["z.html"]));
+    let other_server = catalogue.update(21, &files(&["z.html", "a.html"]));
+    assert_eq!(other_server.added, files(&["a.html", "z.html"]));
+
+    assert_eq!(
+        catalogue.files().collect::<Vec<_>>(),
+        vec![(21, "a.html"), (21, "z.html"), (22, "z.html")]
+    );
+}
@@ -3,14 +3,21 @@ use log::{error, info, warn};
 use messages::high_level_messages::MessageContent::FromClient;
 use messages::{
     client_commands::{MediaClientCommand, MediaClientEvent},
-    high_level_messages::ClientMessage,
+    high_level_messages::{ClientMessage, ServerType},
 };
 use wg_2024::network::NodeId;
 
-use super::MediaClient;
+use super::{
+    file_catalogue::CatalogueEntry, MediaClient, MediaClientExtCommand, MediaClientExtEvent,
+    RecordedCommand, TraceRecord,
+};
+
+/// how many paths other than the current one are reported by `GetRoute`
+const MAX_ALTERNATIVE_ROUTES: usize = 5;
 
 impl MediaClient {
     pub fn handle_command(&mut self, command: MediaClientCommand) {
+        self.record(|| TraceRecord::Command(RecordedCommand::from(&command)));
         match command {
             MediaClientCommand::InitFlooding => self.flood_network(),
             MediaClientCommand::RemoveSender(id) => {
@@ -34,6 +41,7 @@ impl MediaClient {
                     });
                 self.flood_network();
                 self.router.remove_neighbour(id);
+                self.topology.remove_neighbour(id);
             }
             MediaClientCommand::AddSender(id, sender) => {
                 if let std::collections::hash_map::Entry::Vacant(e) = self.packet_send.entry(id) {
@@ -48,6 +56,7 @@ impl MediaClient {
                 }
                 self.flood_network();
                 self.router.add_neighbour(id);
+                self.topology.add_neighbour(id);
             }
             MediaClientCommand::GetServerList => {
                 let server_list = self
@@ -62,7 +71,101 @@ impl MediaClient {
             | MediaClientCommand::AskForFile(id, _) => self.handle_ask(id, command),
         }
     }
+    pub fn handle_ext_command(&mut self, command: MediaClientExtCommand) {
+        self.record(|| TraceRecord::ExtCommand(command.clone()));
+        match command {
+            MediaClientExtCommand::GetTopology => {
+                self.send_ext_controller(MediaClientExtEvent::Topology(self.topology.snapshot()));
+            }
+            MediaClientExtCommand::GetRoute(destination) => {
+                let current = self.router.get_source_routing_header(destination).ok();
+                let alternatives = self
+                    .topology
+                    .routes_to(destination, MAX_ALTERNATIVE_ROUTES + 1)
+                    .into_iter()
+                    .filter(|route| current.as_ref().is_none_or(|header| header.hops != *route))
+                    .take(MAX_ALTERNATIVE_ROUTES)
+                    .collect();
+                self.send_ext_controller(MediaClientExtEvent::Route {
+                    destination,
+                    current,
+                    alternatives,
+                });
+            }
+            MediaClientExtCommand::SetMediaServerStrategy(strategy) => {
+                self.media_selector.set_strategy(strategy);
+            }
+            MediaClientExtCommand::SetTextCachePolicy(policy) => {
+                self.text_cache.set_policy(policy);
+            }
+            MediaClientExtCommand::OpenCachedFile(source_id, file_id) => {
+                match self.text_cache.get(source_id, &file_id) {
+                    Some(cached) => self.open_cached_file(cached),
+                    None => self.send_ext_controller(MediaClientExtEvent::CachedFileMissing {
+                        source_id,
+                        file_id,
+                    }),
+                }
+            }
+            MediaClientExtCommand::GetCatalogue => {
+                let catalogue = self.catalogue();
+                self.send_ext_controller(MediaClientExtEvent::Catalogue(catalogue));
+            }
+            MediaClientExtCommand::SetSizeMismatchPolicy(policy) => {
+                self.size_mismatch_policy = policy;
+            }
+            MediaClientExtCommand::SetOutputMode(mode) => {
+                self.renderer.set_mode(mode);
+            }
+            MediaClientExtCommand::SetProgressive(progressive) => {
+                self.file_assembler.set_partial_files(progressive);
+            }
+            MediaClientExtCommand::PurgeOutput => {
+                let removed = self.renderer.purge_output();
+                self.send_ext_controller(MediaClientExtEvent::OutputPurged { removed });
+            }
+            MediaClientExtCommand::SetFaults(config) => {
+                self.faults.set_config(config);
+                self.send_ext_controller(MediaClientExtEvent::FaultCounts(self.faults.counts()));
+            }
+            MediaClientExtCommand::GetFaultCounts => {
+                self.send_ext_controller(MediaClientExtEvent::FaultCounts(self.faults.counts()));
+            }
+            MediaClientExtCommand::GetMetrics => {
+                let metrics = self.metrics.get_mut().clone();
+                self.send_ext_controller(MediaClientExtEvent::Metrics(metrics));
+            }
+            MediaClientExtCommand::GetMediaCacheStats => {
+                self.send_ext_controller(MediaClientExtEvent::MediaCacheStats(
+                    self.media_cache.stats(),
+                ));
+            }
+        }
+    }
+    /// Files of every text server, with the server they come from
+    fn catalogue(&self) -> Vec<CatalogueEntry> {
+        self.file_catalogue
+            .files()
+            .filter(|(server_id, _)| {
+                self.server_directory
+                    .server_type(*server_id)
+                    .is_none_or(|server_type| matches!(server_type, ServerType::Text))
+            })
+            .map(|(server_id, file_id)| CatalogueEntry {
+                server_id,
+                file_id: file_id.to_owned(),
+                cached: self.text_cache.contains(server_id, file_id),
+            })
+            .collect()
+    }
     fn handle_ask(&mut self, destination: NodeId, command: MediaClientCommand) {
+        if let MediaClientCommand::AskForFile(_, file_id) = &command {
+            if let Some(cached) = self.text_cache.fresh(destination, file_id) {
+                self.metrics.get_mut().text_cache_hits += 1;
+                self.open_cached_file(cached);
+                return;
+            }
+        }
         let Ok(header) = self.router.get_source_routing_header(destination) else {
             self.send_controller(MediaClientEvent::UnreachableNode(destination));
             error!(
@@ -74,12 +177,12 @@ impl MediaClient {
         };
         let client_message = match command {
             MediaClientCommand::AskServerType(_) => ClientMessage::GetServerType,
-            MediaClientCommand::AskFilesList(_) => {
-                self.ask_media_server();
-                ClientMessage::GetFilesList
-            }
+            MediaClientCommand::AskFilesList(_) => ClientMessage::GetFilesList,
             MediaClientCommand::AskForFile(_, file_id) => {
-                self.ask_media_server();
+                self.requested_at.insert(
+                    (Some(destination), file_id.clone()),
+                    self.clock.unix_millis(),
+                );
                 ClientMessage::GetFile(file_id)
             }
             _ => return,
@@ -95,8 +198,7 @@ impl MediaClient {
             //     fragment_packet.session_id,
             //     fragment_packet.get_fragment_index()
             // );
-            self.packet_cache.insert_packet(&fragment_packet);
-            self.send_packet(fragment_packet, None);
+            self.send_fragment(fragment_packet);
         }
     }
 }
@@ -0,0 +1,369 @@
+use colored::Colorize;
+use log::{error, info, warn};
+use messages::high_level_messages::{
+    ClientMessage::{self, GetMedia},
+    Message,
+    MessageContent::{FromClient, FromServer},
+    ServerMessage::{File, FilesList, Media, ServerType},
+};
+use wg_2024::network::NodeId;
+
+use super::{
+    file_assembler::{
+        AddedFileReturn, AssemblerReport, FileAssembler, SizeMismatch, SizeMismatchPolicy,
+    },
+    manifest::FetchInfo,
+    media_server_selector::{Candidate, MediaServerSelectionError},
+    text_cache::CachedTextFile,
+    MediaClient, MediaClientExtEvent,
+};
+
+#[cfg(test)]
+mod test;
+
+impl MediaClient {
+    /// `route` is the one taken by the last fragment of `message`, `[client, .., server]`
+    pub fn handle_message(&mut self, message: Message, route: Vec<NodeId>) {
+        let FromServer(content) = message.content else {
+            return;
+        };
+        match content {
+            ServerType(server_type) => {
+                self.server_directory
+                    .set_server_type(message.source_id, server_type.clone());
+                self.send_controller(
+                    messages::client_commands::MediaClientEvent::ReceveidServerType(
+                        message.source_id,
+                        server_type,
+                    ),
+                );
+                self.notify_directory_changes();
+            }
+            FilesList(files_ids) => {
+                let diff = self.file_catalogue.update(message.source_id, &files_ids);
+                self.send_ext_controller(MediaClientExtEvent::FilesListChanged {
+                    server_id: message.source_id,
+                    added: diff.added,
+                    removed: diff.removed,
+                });
+                self.send_controller(
+                    messages::client_commands::MediaClientEvent::ReceveidFileList(
+                        message.source_id,
+                        self.id,
+                        files_ids,
+                    ),
+                );
+                info!("[MediaClient {}] receveid files_list", self.id);
+                println!("[MediaClient {}] receveid files_list", self.id);
+            }
+            File {
+                file_id,
+                size,
+                content,
+            } => {
+                info!("[MediaClient {}] received file: {file_id}", self.id);
+                if let Err(mismatch) = FileAssembler::check_size(&content, size) {
+                    if !self.handle_size_mismatch(message.source_id, &file_id, mismatch) {
+                        return;
+                    }
+                } else {
+                    self.size_refetches
+                        .remove(&(message.source_id, file_id.clone()));
+                }
+                self.metrics.get_mut().file_bytes += content.len() as u64;
+                self.text_cache
+                    .insert(message.source_id, &file_id, &content, size);
+                let fetch =
+                    self.fetch_info(message.source_id, Some(message.source_id), &file_id, route);
+                self.assemble_file(message.source_id, file_id, content, size, fetch);
+            }
+            Media(media_id, content) => {
+                info!("[MediaClient {} ] received media: {media_id}", self.id);
+                // println!("[MediaClient {} ] received media: {media_id}", self.id);
+                self.metrics.get_mut().media_bytes += content.len() as u64;
+                self.media_selector
+                    .media_received(message.source_id, &media_id);
+                match self.file_assembler.integrity_mismatch(&media_id, &content) {
+                    // asked once more, the assembler reports it if it does not match again
+                    Some(document) if self.integrity_refetches.insert(media_id.clone()) => {
+                        warn!(
+                            "{} [MediaClient {}] media {media_id} does not match its integrity, refetching",
+                            "!!!".yellow(),
+                            self.id
+                        );
+                        self.requested_at.remove(&(None, media_id.clone()));
+                        self.fetch_media(&document, media_id);
+                        return;
+                    }
+                    Some(_) => {
+                        self.integrity_refetches.remove(&media_id);
+                    }
+                    None => {
+                        self.integrity_refetches.remove(&media_id);
+                        self.media_cache.insert(&media_id, content.clone());
+                    }
+                }
+                let fetch = self.fetch_info(message.source_id, None, &media_id, route);
+                self.add_media_file(&media_id, content, fetch);
+            }
+            _ => (),
+        }
+    }
+    /// Reports the mismatch and applies the `SizeMismatchPolicy`
+    ///
+    /// # Returns
+    /// `true` if the file has to be displayed anyway
+    fn handle_size_mismatch(
+        &mut self,
+        source_id: NodeId,
+        file_id: &str,
+        mismatch: SizeMismatch,
+    ) -> bool {
+        warn!(
+            "{} [MediaClient {}] file {source_id}_{file_id} has {} bytes, {} expected",
+            "!!!".yellow(),
+            self.id,
+            mismatch.actual,
+            mismatch.expected
+        );
+        let key = (source_id, file_id.to_owned());
+        let refetching = match self.size_mismatch_policy {
+            SizeMismatchPolicy::Accept | SizeMismatchPolicy::Discard => false,
+            SizeMismatchPolicy::Refetch { max_attempts } => {
+                let attempts = self.size_refetches.entry(key.clone()).or_default();
+                *attempts += 1;
+                *attempts <= max_attempts
+            }
+        };
+        self.send_ext_controller(MediaClientExtEvent::FileSizeMismatch {
+            source_id,
+            file_id: file_id.to_owned(),
+            expected: mismatch.expected,
+            actual: mismatch.actual,
+            refetching,
+        });
+        let request = (Some(source_id), file_id.to_owned());
+        if refetching {
+            if self.send_client_message(source_id, ClientMessage::GetFile(file_id.to_owned())) {
+                // the answer is measured from the new request
+                self.requested_at.insert(request, self.clock.unix_millis());
+            }
+        } else {
+            self.size_refetches.remove(&key);
+            if self.size_mismatch_policy != SizeMismatchPolicy::Accept {
+                self.requested_at.remove(&request);
+            }
+        }
+        self.size_mismatch_policy == SizeMismatchPolicy::Accept
+    }
+    /// Gives a text file to the `FileAssembler` and gets the media it references
+    fn assemble_file(
+        &mut self,
+        source_id: NodeId,
+        file_id: String,
+        content: String,
+        size: usize,
+        fetch: FetchInfo,
+    ) {
+        let assembled = self
+            .file_assembler
+            .add_textfile(source_id, &file_id, content, size, fetch);
+        self.forward_assembler_reports();
+        let document = (source_id, file_id);
+        for file in assembled {
+            let AddedFileReturn::RefToMedia(media_ref) = file else {
+                self.render_file(file);
+                continue;
+            };
+            for (_, media_id) in media_ref {
+                if let Some(content) = self.media_cache.get(&media_id) {
+                    info!("[MediaClient {}] media {media_id} from cache", self.id);
+                    self.metrics.get_mut().media_cache_hits += 1;
+                    self.add_media_file(
+                        &media_id,
+                        content,
+                        FetchInfo::from_cache(self.clock.as_ref()),
+                    );
+                } else {
+                    self.fetch_media(&document, media_id);
+                }
+            }
+        }
+    }
+    fn add_media_file(&mut self, media_id: &str, content: String, fetch: FetchInfo) {
+        let assembled = self.file_assembler.add_media_file(media_id, content, fetch);
+        self.forward_assembler_reports();
+        for file in assembled {
+            self.render_file(file);
+        }
+    }
+    /// Writes a document given by the `FileAssembler` and notifies the controller
+    fn render_file(&mut self, file: AddedFileReturn) {
+        if let AddedFileReturn::CompleteFile { manifest, .. } = &file {
+            self.media_selector
+                .document_completed(&(manifest.source_id, manifest.file_id.clone()));
+            let assembly_ms = manifest
+                .completed_at
+                .saturating_sub(manifest.fetch.received_at);
+            self.metrics.get_mut().assembly_time.record(assembly_ms);
+        }
+        if let Some(update) = self.renderer.render(file) {
+            self.send_ext_controller(MediaClientExtEvent::DocumentUpdated(update));
+        }
+    }
+    /// Writes the documents given up by the `Renderer` without their missing media
+    pub(super) fn give_up_partial_documents(&mut self) {
+        for (source_id, file_id) in self.renderer.give_up(self.clock.now()) {
+            if let Some(file) = self.file_assembler.partial(source_id, &file_id) {
+                self.render_file(file);
+            }
+        }
+    }
+    /// Where and when the answer to the request `(request_server, name)` came from
+    fn fetch_info(
+        &mut self,
+        server_id: NodeId,
+        request_server: Option<NodeId>,
+        name: &str,
+        route: Vec<NodeId>,
+    ) -> FetchInfo {
+        let requested_at = self.requested_at.remove(&(request_server, name.to_owned()));
+        let received_at = self.clock.unix_millis();
+        if let Some(requested_at) = requested_at {
+            self.metrics
+                .get_mut()
+                .server_latency(server_id, received_at.saturating_sub(requested_at));
+        }
+        FetchInfo {
+            server_id: Some(server_id),
+            route,
+            requested_at,
+            received_at,
+        }
+    }
+    fn forward_assembler_reports(&mut self) {
+        for report in self.file_assembler.take_reports() {
+            warn!("{} [MediaClient {}] {report:?}", "!!!".yellow(), self.id);
+            if let AssemblerReport::IntegrityMismatch { media_id, .. } = &report {
+                // a cached copy taken for another document does not match either
+                self.media_cache.remove(media_id);
+            }
+            self.send_ext_controller(MediaClientExtEvent::AssemblerReport(report));
+        }
+    }
+    /// Displays a text file from the `TextCache`, without asking the server
+    pub fn open_cached_file(&mut self, cached: CachedTextFile) {
+        info!(
+            "[MediaClient {}] opening cached file {}_{}",
+            self.id, cached.source_id, cached.file_id
+        );
+        self.send_ext_controller(MediaClientExtEvent::CachedFileOpened {
+            source_id: cached.source_id,
+            file_id: cached.file_id.clone(),
+            fetched_at: cached.fetched_at,
+        });
+        self.assemble_file(
+            cached.source_id,
+            cached.file_id,
+            cached.content,
+            cached.size,
+            FetchInfo::from_cache(self.clock.as_ref()),
+        );
+    }
+    /// Asks their type to the servers that are not in the directory or whose entry expired
+    pub fn discover_servers(&mut self) {
+        let servers = self.router.get_server_list();
+        for server in self.server_directory.servers_to_query(servers) {
+            // unknown or expired, what was measured may not apply anymore
+            self.media_selector.forget_server(server);
+            self.send_client_message(server, ClientMessage::GetServerType);
+        }
+        self.notify_directory_changes();
+    }
+    fn notify_directory_changes(&mut self) {
+        let Some(directory) = self.server_directory.take_changes() else {
+            return;
+        };
+        self.send_ext_controller(MediaClientExtEvent::ServerDirectoryChanged(directory));
+        self.media_selector
+            .retain_servers(&self.server_directory.media_servers());
+        if !self.server_directory.media_servers().is_empty() {
+            for (document, media_id) in std::mem::take(&mut self.unfetched_media) {
+                self.fetch_media(&document, media_id);
+            }
+        }
+    }
+    /// Sends `GetMedia` to the media server chosen by the `MediaServerSelector`.
+    /// If there is none the media is kept until the server directory changes.
+    fn fetch_media(&mut self, document: &(NodeId, String), media_id: String) {
+        let destination = match self.select_media_server(document) {
+            Ok(destination) => destination,
+            Err(e) => {
+                error!(
+                    "{} [MediaClient {}] cannot fetch media {media_id}: {e}",
+                    "✗".red(),
+                    self.id
+                );
+                self.send_ext_controller(MediaClientExtEvent::MediaServerUnavailable {
+                    media_id: media_id.clone(),
+                    error: e,
+                });
+                self.unfetched_media.push((document.clone(), media_id));
+                return;
+            }
+        };
+        info!(
+            "[MediaClient: {}] fetching ref: {destination}, {media_id}",
+            self.id
+        );
+        if self.send_client_message(destination, GetMedia(media_id.clone())) {
+            self.media_selector.request_sent(destination, &media_id);
+            self.requested_at
+                .insert((None, media_id), self.clock.unix_millis());
+        }
+    }
+    fn select_media_server(
+        &mut self,
+        document: &(NodeId, String),
+    ) -> Result<NodeId, MediaServerSelectionError> {
+        let media_servers = self.server_directory.media_servers();
+        if media_servers.is_empty() {
+            return Err(MediaServerSelectionError::NoMediaServer);
+        }
+        let candidates = media_servers
+            .iter()
+            .filter_map(|&id| {
+                let header = self.router.get_source_routing_header(id).ok()?;
+                Some(Candidate {
+                    id,
+                    hops: header.hops.len(),
+                })
+            })
+            .collect::<Vec<Candidate>>();
+        if candidates.is_empty() {
+            return Err(MediaServerSelectionError::NoReachableMediaServer(
+                media_servers,
+            ));
+        }
+        self.media_selector.select(&candidates, document)
+    }
+    /// # Returns
+    /// `false` if `destination` is unreachable
+    fn send_client_message(&mut self, destination: NodeId, client_message: ClientMessage) -> bool {
+        let Ok(header) = self.router.get_source_routing_header(destination) else {
+            self.send_controller(
+                messages::client_commands::MediaClientEvent::UnreachableNode(destination),
+            );
+            return false;
+        };
+        let message = self.message_factory.get_message_from_message_content(
+            FromClient(client_message),
+            &header,
+            destination,
+        );
+        for fragment in message {
+            self.send_fragment(fragment);
+        }
+        true
+    }
+}
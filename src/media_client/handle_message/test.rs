@@ -0,0 +1,186 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use assembler::HighLevelMessageFactory;
+use base64::{engine::general_purpose, Engine};
+use crossbeam_channel::{unbounded, Receiver};
+use messages::high_level_messages::{self, ServerMessage};
+use sha2::{Digest, Sha256};
+use wg_2024::{
+    network::SourceRoutingHeader,
+    packet::{FloodResponse, NodeType, Packet, PacketType},
+};
+
+use super::*;
+use crate::media_client::{
+    manifest::MANIFEST_FILE, test_util::test_config, Clock, ManualClock, MediaClientConfig,
+};
+
+const CLIENT: NodeId = 1;
+const DRONE: NodeId = 11;
+const TEXT_SERVER: NodeId = 21;
+const MEDIA_SERVER: NodeId = 22;
+
+/// Client that knows the path `CLIENT - DRONE - TEXT_SERVER`
+fn client(name: &str) -> (MediaClient, Arc<ManualClock>, Receiver<Packet>) {
+    let clock = Arc::new(ManualClock::default());
+    let (drone_send, drone_recv) = unbounded();
+    let mut client = MediaClient::new(
+        CLIENT,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(DRONE, drone_send)]),
+        MediaClientConfig {
+            clock: clock.clone(),
+            ..test_config(&format!("handle_message_{name}"))
+        },
+    );
+    client.handle_packet(flood_response(TEXT_SERVER));
+    (client, clock, drone_recv)
+}
+
+/// `FloodResponse` with the path `CLIENT - DRONE - server`
+fn flood_response(server: NodeId) -> Packet {
+    Packet {
+        routing_header: SourceRoutingHeader::new(vec![server, DRONE, CLIENT], 2),
+        session_id: 0,
+        pack_type: PacketType::FloodResponse(FloodResponse {
+            flood_id: 1,
+            path_trace: vec![
+                (CLIENT, NodeType::Client),
+                (DRONE, NodeType::Drone),
+                (server, NodeType::Server),
+            ],
+        }),
+    }
+}
+
+/// Fragments of `message` sent by `server` through `DRONE`, as the client receives them
+fn from_server(server: NodeId, message: ServerMessage) -> Vec<Packet> {
+    HighLevelMessageFactory::new(server, NodeType::Server)
+        .get_message_from_message_content(
+            FromServer(message),
+            &SourceRoutingHeader::with_first_hop(vec![server, DRONE, CLIENT]),
+            CLIENT,
+        )
+        .into_iter()
+        .map(|mut fragment| {
+            fragment.routing_header.hop_index = 2;
+            fragment
+        })
+        .collect()
+}
+
+#[test]
+fn test_size_refetch_is_measured_from_the_new_request() {
+    let (mut client, clock, _drone_recv) = client("refetch");
+    let request = (Some(TEXT_SERVER), "a.html".to_owned());
+    client
+        .requested_at
+        .insert(request.clone(), clock.unix_millis());
+
+    clock.advance(Duration::from_secs(2));
+    let wrong_size = ServerMessage::File {
+        file_id: "a.html".to_owned(),
+        size: 100,
+        content: "<p>a</p>".to_owned(),
+    };
+    for fragment in from_server(TEXT_SERVER, wrong_size) {
+        client.handle_packet(fragment);
+    }
+    assert_eq!(client.requested_at.get(&request), Some(&2_000));
+
+    client.size_mismatch_policy = SizeMismatchPolicy::Discard;
+    let wrong_size = ServerMessage::File {
+        file_id: "a.html".to_owned(),
+        size: 100,
+        content: "<p>a</p>".to_owned(),
+    };
+    for fragment in from_server(TEXT_SERVER, wrong_size) {
+        client.handle_packet(fragment);
+    }
+    assert!(client.requested_at.is_empty());
+}
+
+#[test]
+fn test_corrupted_media_is_not_cached_and_fetched_again() {
+    let (mut client, _clock, drone_recv) = client("integrity");
+    client.handle_packet(flood_response(MEDIA_SERVER));
+    let server_type = ServerMessage::ServerType(high_level_messages::ServerType::Media);
+    for fragment in from_server(MEDIA_SERVER, server_type) {
+        client.handle_packet(fragment);
+    }
+    let media = general_purpose::STANDARD.encode(b"good image");
+    let digest = general_purpose::STANDARD.encode(Sha256::digest(b"good image"));
+    let content = format!("<img src=\"a.jpg\" integrity=\"sha256-{digest}\">");
+    let document = ServerMessage::File {
+        file_id: "a.html".to_owned(),
+        size: content.len(),
+        content,
+    };
+    let get_media_sent = || {
+        drone_recv
+            .try_iter()
+            .filter(|packet| {
+                packet.routing_header.hops.last() == Some(&MEDIA_SERVER)
+                    && matches!(&packet.pack_type, PacketType::MsgFragment(fragment) if fragment.fragment_index == 0)
+            })
+            .count()
+    };
+    let _ = get_media_sent();
+
+    for fragment in from_server(TEXT_SERVER, document) {
+        client.handle_packet(fragment);
+    }
+    assert_eq!(get_media_sent(), 1);
+
+    let corrupted = general_purpose::STANDARD.encode(b"bad image");
+    for fragment in from_server(
+        MEDIA_SERVER,
+        ServerMessage::Media("a.jpg".to_owned(), corrupted),
+    ) {
+        client.handle_packet(fragment);
+    }
+    assert_eq!(get_media_sent(), 1);
+    assert_eq!(client.media_cache.get("a.jpg"), None);
+
+    for fragment in from_server(
+        MEDIA_SERVER,
+        ServerMessage::Media("a.jpg".to_owned(), media.clone()),
+    ) {
+        client.handle_packet(fragment);
+    }
+    assert_eq!(get_media_sent(), 0);
+    assert_eq!(client.media_cache.get("a.jpg"), Some(media));
+    assert!(client.integrity_refetches.is_empty());
+}
+
+#[test]
+fn test_fetch_route_is_the_one_taken_by_the_answer() {
+    let (client, _clock, _drone_recv) = client("route");
+    let (ext_send, ext_recv) = unbounded();
+    let mut client = client.with_ext_controller(ext_send, unbounded().1);
+    let answer = ServerMessage::File {
+        file_id: "a.html".to_owned(),
+        size: 8,
+        content: "<p>a</p>".to_owned(),
+    };
+    // the router knows `CLIENT - DRONE - TEXT_SERVER`, the answer comes through 12
+    for mut fragment in from_server(TEXT_SERVER, answer) {
+        fragment.routing_header.hops = vec![TEXT_SERVER, 12, CLIENT];
+        client.handle_packet(fragment);
+    }
+
+    let Some(MediaClientExtEvent::DocumentUpdated(update)) = ext_recv
+        .try_iter()
+        .find(|event| matches!(event, MediaClientExtEvent::DocumentUpdated(_)))
+    else {
+        panic!("document not rendered");
+    };
+    let manifest = std::fs::read_to_string(update.path.with_file_name(MANIFEST_FILE)).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    assert_eq!(
+        manifest["fetch"]["route"],
+        serde_json::json!([CLIENT, 12, TEXT_SERVER])
+    );
+}
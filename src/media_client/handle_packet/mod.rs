@@ -8,13 +8,15 @@ use wg_2024::{
     packet::{Ack, FloodRequest, FloodResponse, Nack, NackType, NodeType, Packet},
 };
 
-use super::MediaClient;
+use super::{MediaClient, MediaClientExtEvent, TraceRecord};
 
 #[cfg(test)]
 mod test;
 
 impl MediaClient {
     pub fn handle_packet(&mut self, packet: Packet) {
+        self.record(|| TraceRecord::PacketIn(packet.clone()));
+        self.metrics.get_mut().packet_received(&packet);
         match packet.pack_type {
             wg_2024::packet::PacketType::MsgFragment(ref fragment) => {
                 if self.check_packet(&packet, Some(fragment.fragment_index)) {
@@ -24,7 +26,9 @@ impl MediaClient {
                         packet.session_id,
                         packet.routing_header.hops[0],
                     ) {
-                        self.handle_message(message);
+                        let mut route = packet.routing_header.hops.clone();
+                        route.reverse();
+                        self.handle_message(message, route);
                     }
                 } else {
                     let mut rev = packet.clone().routing_header.hops;
@@ -44,17 +48,23 @@ impl MediaClient {
                 // self.message_factory.received_ack(ack, packet.session_id);
                 self.packet_cache
                     .take_packet((packet.session_id, ack.fragment_index));
+                self.fragment_settled(packet.session_id, ack.fragment_index);
             }
             wg_2024::packet::PacketType::Nack(nack) => {
                 // println!("[mediaclient {}] packet dropped: {}", self.id, packet);
                 self.handle_nack(nack, packet.session_id, packet.routing_header.hops[0]);
             }
             wg_2024::packet::PacketType::FloodRequest(request) => {
+                if !self.faults.answer_flood_request() {
+                    return;
+                }
                 let res = self.get_flood_response(request, packet.session_id);
                 self.send_packet(res, None);
             }
             wg_2024::packet::PacketType::FloodResponse(response) => {
                 self.router.handle_flood_response(&response);
+                self.topology.add_path_trace(&response.path_trace);
+                self.discover_servers();
             }
         }
     }
@@ -68,6 +78,7 @@ impl MediaClient {
                     self.id,
                 );
                 self.router.drone_crashed(crashed_id);
+                self.topology.drone_crashed(crashed_id);
                 self.resend_for_nack(session_id, nack.fragment_index, crashed_id);
             }
             wg_2024::packet::NackType::DestinationIsDrone => {
@@ -80,6 +91,7 @@ impl MediaClient {
             }
             wg_2024::packet::NackType::Dropped => {
                 error!("{} [MediaClient. {}]: Nack dropped", "✗".red(), self.id);
+                self.topology.record_drop(nack_src);
                 self.resend_for_nack(session_id, nack.fragment_index, nack_src);
             }
             wg_2024::packet::NackType::UnexpectedRecipient(id) => {
@@ -96,26 +108,72 @@ impl MediaClient {
         let Some((packet, freq)) = self.packet_cache.get_value((session_id, fragment_index)) else {
             println!("[MediaClient {}] error extracting from cache ({session_id}, {fragment_index}) nack_src: {nack_src}", self.id);
             self.send_controller(ErrorPacketCache(session_id, fragment_index));
+            // it cannot be sent again, the session is abandoned for this fragment
+            self.fragment_settled(session_id, fragment_index);
             return;
         };
         self.router.dropped_fragment(nack_src);
         let Some(destination) = packet.routing_header.destination() else {
             return;
         };
+        self.media_selector.record_loss(destination);
         let Ok(new_header) = self.router.get_source_routing_header(destination) else {
             self.send_controller(UnreachableNode(destination));
+            self.metrics.get_mut().retransmissions += 1;
             self.send_packet(packet, None);
             return;
         };
+        if new_header.hops != packet.routing_header.hops {
+            self.notify_route_change(
+                session_id,
+                destination,
+                &packet.routing_header.hops,
+                &new_header.hops,
+            );
+        }
         let new_packet = Packet {
             routing_header: new_header,
             ..packet
         };
+        self.metrics.get_mut().retransmissions += 1;
         self.send_packet(new_packet, None);
         if freq > 100 {
             self.flood_network();
         }
     }
+    /// Forgets the session once none of its fragments can be acked anymore
+    fn fragment_settled(&mut self, session_id: u64, fragment_index: u64) {
+        let Some(unacked) = self.unacked_fragments.get_mut(&session_id) else {
+            return;
+        };
+        unacked.remove(&fragment_index);
+        if unacked.is_empty() {
+            self.unacked_fragments.remove(&session_id);
+            self.session_routes.remove(&session_id);
+        }
+    }
+    fn notify_route_change(
+        &mut self,
+        session_id: u64,
+        destination: NodeId,
+        previous: &[NodeId],
+        current: &[NodeId],
+    ) {
+        if self
+            .session_routes
+            .get(&session_id)
+            .is_some_and(|route| route == current)
+        {
+            return;
+        }
+        self.session_routes.insert(session_id, current.to_vec());
+        self.send_ext_controller(MediaClientExtEvent::RouteChanged {
+            session_id,
+            destination,
+            previous: previous.to_vec(),
+            current: current.to_vec(),
+        });
+    }
     fn check_packet(&self, packet: &Packet, fragment_index: Option<u64>) -> bool {
         let hop_index = packet.routing_header.hop_index;
         if self.id != packet.routing_header.hops[hop_index] {
@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crossbeam_channel::unbounded;
 
+use wg_2024::packet::{Fragment, PacketType, FRAGMENT_DSIZE};
+
 use super::*;
+use crate::media_client::{test_util::test_config, MediaClientConfig};
 
 #[test]
 fn test_get_flood_response() {
@@ -12,6 +15,7 @@ fn test_get_flood_response() {
         unbounded().1,
         unbounded().1,
         HashMap::new(),
+        MediaClientConfig::default(),
     );
 
     let flood_request = FloodRequest {
@@ -48,3 +52,93 @@ fn test_get_flood_response() {
 
     assert_eq!(flood_response, expect);
 }
+
+fn fragment(session_id: u64, fragment_index: u64) -> Packet {
+    Packet::new_fragment(
+        SourceRoutingHeader::with_first_hop(vec![1, 11, 21]),
+        session_id,
+        Fragment {
+            fragment_index,
+            total_n_fragments: 2,
+            length: 0,
+            data: [0; FRAGMENT_DSIZE],
+        },
+    )
+}
+
+fn from_drone(session_id: u64, pack_type: PacketType) -> Packet {
+    Packet {
+        routing_header: SourceRoutingHeader::new(vec![11, 1], 1),
+        session_id,
+        pack_type,
+    }
+}
+
+#[test]
+fn test_session_route_is_forgotten_once_acked() {
+    let (drone_send, _drone_recv) = unbounded();
+    let mut client = MediaClient::new(
+        1,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(11, drone_send)]),
+        MediaClientConfig::default(),
+    );
+    client.send_fragment(fragment(5, 0));
+    client.send_fragment(fragment(5, 1));
+    client.notify_route_change(5, 21, &[1, 11, 21], &[1, 12, 21]);
+
+    client.handle_packet(from_drone(5, PacketType::Ack(Ack { fragment_index: 0 })));
+    client.handle_packet(from_drone(5, PacketType::Ack(Ack { fragment_index: 0 })));
+    assert!(client.session_routes.contains_key(&5));
+    client.handle_packet(from_drone(5, PacketType::Ack(Ack { fragment_index: 1 })));
+    assert!(!client.session_routes.contains_key(&5));
+    assert!(client.unacked_fragments.is_empty());
+}
+
+#[test]
+fn test_session_route_is_forgotten_when_abandoned() {
+    let mut client = MediaClient::new(
+        1,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::new(),
+        MediaClientConfig::default(),
+    );
+    client.unacked_fragments.insert(6, HashSet::from([0]));
+    client.notify_route_change(6, 21, &[1, 11, 21], &[1, 12, 21]);
+
+    // the fragment is not in the `PacketCache`, it cannot be sent again
+    let nack = Nack {
+        fragment_index: 0,
+        nack_type: NackType::Dropped,
+    };
+    client.handle_packet(from_drone(6, PacketType::Nack(nack)));
+    assert!(client.session_routes.is_empty());
+    assert!(client.unacked_fragments.is_empty());
+}
+
+#[test]
+fn test_nack_without_a_new_route_counts_the_retransmission() {
+    let (drone_send, drone_recv) = unbounded();
+    let mut client = MediaClient::new(
+        1,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(11, drone_send)]),
+        test_config("handle_packet_nack"),
+    );
+    client.send_fragment(fragment(7, 0));
+
+    // the topology is empty, the fragment is sent again on its old route
+    let nack = Nack {
+        fragment_index: 0,
+        nack_type: NackType::Dropped,
+    };
+    client.handle_packet(from_drone(7, PacketType::Nack(nack)));
+    assert_eq!(client.metrics.borrow().retransmissions, 1);
+    assert_eq!(drone_recv.try_iter().count(), 2);
+}
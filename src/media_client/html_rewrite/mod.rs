@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+
+use super::sanitize::safe_chars;
+
+#[cfg(test)]
+mod test;
+
+/// extensions `image` knows how to save, anything else is saved as jpeg
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "gif", "webp", "bmp"];
+
+/// Chooses the names used to save the media of a document, next to its html.
+///
+/// # Returns
+/// `(media_id, local name)`, local names are unique and contain no path.
+/// Ids are processed in order, so the same ids always get the same names.
+pub fn local_media_names<'a>(
+    media_ids: impl IntoIterator<Item = &'a String>,
+) -> HashMap<String, String> {
+    let mut media_ids = media_ids.into_iter().collect::<Vec<&String>>();
+    media_ids.sort_unstable();
+    let mut taken = HashSet::new();
+    let mut local_names = HashMap::new();
+    for media_id in media_ids {
+        if local_names.contains_key(media_id) {
+            continue;
+        }
+        let local_name = local_media_name(media_id, &taken);
+        taken.insert(local_name.clone());
+        local_names.insert(media_id.clone(), local_name);
+    }
+    local_names
+}
+
+/// Last component of `media_id`, restricted to `[A-Za-z0-9._-]`,
+/// with an image extension and not in `taken`
+fn local_media_name(media_id: &str, taken: &HashSet<String>) -> String {
+    let file_name = safe_chars(media_id.rsplit(['/', '\\']).next().unwrap_or_default());
+    let file_name = file_name.trim_start_matches('.');
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension))
+            if IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()) =>
+        {
+            (stem, extension)
+        }
+        _ => (file_name, "jpg"),
+    };
+    let stem = if stem.is_empty() { "media" } else { stem };
+
+    let mut local_name = format!("{stem}.{extension}");
+    let mut n = 1;
+    while taken.contains(&local_name) {
+        local_name = format!("{stem}-{n}.{extension}");
+        n += 1;
+    }
+    local_name
+}
+
+/// Replaces the `src` of every `<img>` for which `replace` returns a new value,
+/// leaving the rest of the html untouched.
+///
+/// Comments and the quoted values of attributes are skipped, so only real `<img>` tags match.
+pub fn rewrite_img_src(html: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let mut rewritten = String::with_capacity(html.len());
+    let mut copied = 0;
+    while let Some(found) = html[copied..].find('<') {
+        let start = copied + found;
+        let end = if html[start..].starts_with("<!--") {
+            html[start..]
+                .find("-->")
+                .map_or(html.len(), |i| start + i + "-->".len())
+        } else {
+            tag_end(html, start)
+        };
+        rewritten.push_str(&html[copied..start]);
+        let tag = &html[start..end];
+        if is_img_tag(tag) {
+            rewritten.push_str(&rewrite_src_in_tag(tag, &mut replace));
+        } else {
+            rewritten.push_str(tag);
+        }
+        copied = end;
+    }
+    rewritten.push_str(&html[copied..]);
+    rewritten
+}
+
+/// `<img` followed by whitespace, `/` or `>`, in any case
+fn is_img_tag(tag: &str) -> bool {
+    tag.get(.."<img".len())
+        .is_some_and(|name| name.eq_ignore_ascii_case("<img"))
+        && tag["<img".len()..].starts_with(|c: char| c.is_whitespace() || c == '/' || c == '>')
+}
+
+/// Index after the `>` closing the tag opened at `start`, ignoring the ones in quoted values
+fn tag_end(html: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (i, c) in html[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, '>') => return start + i + 1,
+            _ => (),
+        }
+    }
+    html.len()
+}
+
+fn rewrite_src_in_tag(tag: &str, replace: &mut impl FnMut(&str) -> Option<String>) -> String {
+    let is_name_end = |c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/';
+    let mut position = "<img".len();
+    loop {
+        let rest = &tag[position..];
+        let attribute = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if attribute.is_empty() || attribute.starts_with('>') {
+            return tag.to_owned();
+        }
+        let name_start = tag.len() - attribute.len();
+        let name_end = attribute
+            .find(is_name_end)
+            .map_or(tag.len(), |i| name_start + i);
+        let after_name = tag[name_end..].trim_start();
+        let Some(value) = after_name.strip_prefix('=') else {
+            position = tag.len() - after_name.len();
+            continue;
+        };
+        let value = value.trim_start();
+        let value_start = tag.len() - value.len();
+        let (value_start, value_end, next) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let start = value_start + 1;
+                let end = tag[start..].find(quote).map_or(tag.len(), |i| start + i);
+                (start, end, (end + 1).min(tag.len()))
+            }
+            _ => {
+                let end = value
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .map_or(tag.len(), |i| value_start + i);
+                (value_start, end, end)
+            }
+        };
+        if tag[name_start..name_end].eq_ignore_ascii_case("src") {
+            return match replace(&tag[value_start..value_end]) {
+                Some(new_value) => {
+                    format!("{}{new_value}{}", &tag[..value_start], &tag[value_end..])
+                }
+                None => tag.to_owned(),
+            };
+        }
+        position = next;
+    }
+}
+
+/// Adds a `<meta http-equiv="refresh">` so that the viewer reloads the page every `seconds`,
+/// right after `<head>` if there is one
+pub fn with_refresh(html: &str, seconds: u32) -> String {
+    let meta = format!("<meta http-equiv=\"refresh\" content=\"{seconds}\">");
+    let lower = html.to_ascii_lowercase();
+    let head_end = lower
+        .match_indices("<head")
+        .map(|(start, _)| start)
+        .find(|&start| {
+            lower[start + "<head".len()..].starts_with(|c: char| c == '>' || c.is_whitespace())
+        })
+        .and_then(|start| Some(start + html[start..].find('>')? + 1));
+    match head_end {
+        Some(end) => format!("{}{meta}{}", &html[..end], &html[end..]),
+        None => format!("{meta}{html}"),
+    }
+}
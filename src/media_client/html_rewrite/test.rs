@@ -0,0 +1,49 @@
+use super::*;
+
+fn to_local(src: &str) -> Option<String> {
+    (src == "a.png").then(|| "local.png".to_owned())
+}
+
+#[test]
+fn test_rewrite_img_src() {
+    assert_eq!(
+        rewrite_img_src(r#"<p><img src="a.png"></p>"#, to_local),
+        r#"<p><img src="local.png"></p>"#
+    );
+    assert_eq!(
+        rewrite_img_src("<IMG alt='x' SRC=a.png />", to_local),
+        "<IMG alt='x' SRC=local.png />"
+    );
+    assert_eq!(
+        rewrite_img_src("<img\nsrc = 'a.png' >", to_local),
+        "<img\nsrc = 'local.png' >"
+    );
+    assert_eq!(
+        rewrite_img_src(r#"<img src="b.png">"#, to_local),
+        r#"<img src="b.png">"#
+    );
+}
+
+#[test]
+fn test_rewrite_img_src_ignores_other_tags() {
+    let html = r#"<imgfoo src="a.png"><image src="a.png"><img-x src="a.png">"#;
+    assert_eq!(rewrite_img_src(html, to_local), html);
+}
+
+#[test]
+fn test_rewrite_img_src_ignores_quoted_text() {
+    let html = r#"<a title="<img src=a.png>">link</a><!-- <img src="a.png"> -->"#;
+    assert_eq!(rewrite_img_src(html, to_local), html);
+
+    assert_eq!(
+        rewrite_img_src(
+            r#"<img alt="x src=a.png" data-src="a.png" src="a.png">"#,
+            to_local
+        ),
+        r#"<img alt="x src=a.png" data-src="a.png" src="local.png">"#
+    );
+    assert_eq!(
+        rewrite_img_src(r#"<img alt="1 > 0" src="a.png">"#, to_local),
+        r#"<img alt="1 > 0" src="local.png">"#
+    );
+}
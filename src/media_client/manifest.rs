@@ -0,0 +1,57 @@
+use serde::Serialize;
+use wg_2024::network::NodeId;
+
+use super::clock::Clock;
+
+/// name of the manifest written in every bundle
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Where and when a text file or a media was received
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FetchInfo {
+    /// `None` if it was taken from a cache
+    pub server_id: Option<NodeId>,
+    /// route the answer took from the server, reversed: `[client, .., server]`
+    pub route: Vec<NodeId>,
+    /// milliseconds since `UNIX_EPOCH`, `None` if it was not asked to a server
+    pub requested_at: Option<u64>,
+    /// milliseconds since `UNIX_EPOCH`
+    pub received_at: u64,
+}
+
+impl FetchInfo {
+    pub fn from_cache(clock: &dyn Clock) -> Self {
+        Self {
+            received_at: clock.unix_millis(),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MediaManifest {
+    pub media_id: String,
+    /// name of the file next to the html
+    pub local_name: String,
+    /// size of the decoded media, `None` if it is not valid base64
+    pub bytes: Option<usize>,
+    /// hex sha256 of the decoded media
+    pub sha256: Option<String>,
+    pub fetch: FetchInfo,
+}
+
+/// Machine-readable description of a bundle, written next to its html as `MANIFEST_FILE`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BundleManifest {
+    pub source_id: NodeId,
+    pub file_id: String,
+    /// `size` sent with the file
+    pub advertised_size: usize,
+    /// size of the content received
+    pub bytes: usize,
+    pub fetch: FetchInfo,
+    /// milliseconds since `UNIX_EPOCH` when the last media arrived
+    pub completed_at: u64,
+    /// sorted by `media_id`
+    pub media: Vec<MediaManifest>,
+}
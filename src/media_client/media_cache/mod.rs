@@ -0,0 +1,170 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    fs, io,
+    path::PathBuf,
+};
+
+use log::error;
+
+#[cfg(test)]
+mod test;
+
+pub const DEFAULT_MEDIA_CACHE_BUDGET: usize = 64 * 1024 * 1024;
+/// directory inside the output directory of the client, see `MediaClientConfig::output_dir`
+pub const MEDIA_CACHE_DIR: &str = ".media_cache";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MediaCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub insertions: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub bytes: usize,
+    pub budget: usize,
+}
+
+struct CacheEntry {
+    content: String,
+    last_used: u64,
+}
+
+/// LRU cache of the base64 media contents, shared by every document.
+///
+/// The byte budget applies to the entries kept in memory, persisted entries
+/// stay on disk and are loaded back on a miss.
+pub struct MediaCache {
+    entries: HashMap<String, CacheEntry>,
+    /// `last_used` -> `media_id`, least recently used first
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    used_bytes: usize,
+    budget_bytes: usize,
+    persist_dir: Option<PathBuf>,
+    stats: MediaCacheStats,
+}
+
+impl Default for MediaCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEDIA_CACHE_BUDGET)
+    }
+}
+
+impl MediaCache {
+    #[must_use]
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            used_bytes: 0,
+            budget_bytes,
+            persist_dir: None,
+            stats: MediaCacheStats::default(),
+        }
+    }
+    /// Cache that also keeps every entry in `dir`
+    ///
+    /// # Errors
+    /// Returns an error if `dir` cannot be created
+    pub fn persisted(budget_bytes: usize, dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            persist_dir: Some(dir),
+            ..Self::new(budget_bytes)
+        })
+    }
+    pub fn get(&mut self, media_id: &str) -> Option<String> {
+        if let Some(entry) = self.entries.get_mut(media_id) {
+            self.recency.remove(&entry.last_used);
+            self.tick += 1;
+            entry.last_used = self.tick;
+            self.recency.insert(self.tick, media_id.to_owned());
+            self.stats.hits += 1;
+            return Some(entry.content.clone());
+        }
+        let Some(content) = self.load(media_id) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.insert_in_memory(media_id, content.clone());
+        Some(content)
+    }
+    pub fn insert(&mut self, media_id: &str, content: String) {
+        self.stats.insertions += 1;
+        self.store(media_id, &content);
+        self.insert_in_memory(media_id, content);
+    }
+    /// Drops `media_id` from memory and from disk
+    pub fn remove(&mut self, media_id: &str) {
+        if let Some(old) = self.entries.remove(media_id) {
+            self.recency.remove(&old.last_used);
+            self.used_bytes -= old.content.len();
+        }
+        if let Some(path) = self.path(media_id) {
+            let _ = fs::remove_file(path);
+        }
+    }
+    pub fn stats(&self) -> MediaCacheStats {
+        MediaCacheStats {
+            entries: self.entries.len(),
+            bytes: self.used_bytes,
+            budget: self.budget_bytes,
+            ..self.stats
+        }
+    }
+    fn insert_in_memory(&mut self, media_id: &str, content: String) {
+        if let Some(old) = self.entries.remove(media_id) {
+            self.recency.remove(&old.last_used);
+            self.used_bytes -= old.content.len();
+        }
+        if content.len() > self.budget_bytes {
+            return;
+        }
+        self.tick += 1;
+        self.used_bytes += content.len();
+        self.recency.insert(self.tick, media_id.to_owned());
+        self.entries.insert(
+            media_id.to_owned(),
+            CacheEntry {
+                content,
+                last_used: self.tick,
+            },
+        );
+        while self.used_bytes > self.budget_bytes {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.used_bytes -= evicted.content.len();
+                self.stats.evictions += 1;
+            }
+        }
+    }
+    fn path(&self, media_id: &str) -> Option<PathBuf> {
+        Some(self.persist_dir.as_ref()?.join(hex_encode(media_id)))
+    }
+    fn load(&self, media_id: &str) -> Option<String> {
+        fs::read_to_string(self.path(media_id)?).ok()
+    }
+    fn store(&self, media_id: &str, content: &str) {
+        let Some(path) = self.path(media_id) else {
+            return;
+        };
+        let _ = fs::write(path, content).inspect_err(|e| {
+            error!("[mediaclient] error persisting media {media_id}: {e}");
+        });
+    }
+}
+
+/// Lowercase hex of `bytes`, also used as file name that cannot escape the cache directory
+pub fn hex_encode(bytes: impl AsRef<[u8]>) -> String {
+    let bytes = bytes.as_ref();
+    let mut encoded = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(encoded, "{byte:02x}");
+    }
+    encoded
+}
@@ -0,0 +1,78 @@
+use super::*;
+use crate::media_client::test_util::test_dir;
+
+#[test]
+fn test_least_recently_used_is_evicted_first() {
+    let mut cache = MediaCache::new(10);
+    cache.insert("a", "aaaa".to_owned());
+    cache.insert("b", "bbbb".to_owned());
+    assert_eq!(cache.get("a").as_deref(), Some("aaaa"));
+
+    cache.insert("c", "cccc".to_owned());
+    assert_eq!(cache.get("b"), None);
+    assert_eq!(cache.get("a").as_deref(), Some("aaaa"));
+    assert_eq!(cache.get("c").as_deref(), Some("cccc"));
+    assert_eq!(
+        cache.stats(),
+        MediaCacheStats {
+            hits: 3,
+            misses: 1,
+            insertions: 3,
+            evictions: 1,
+            entries: 2,
+            bytes: 8,
+            budget: 10,
+        }
+    );
+}
+
+#[test]
+fn test_byte_budget_is_respected() {
+    let mut cache = MediaCache::new(10);
+    for (i, media_id) in ["a", "b", "c", "d", "e"].into_iter().enumerate() {
+        cache.insert(media_id, "x".repeat(i + 1));
+        assert!(cache.stats().bytes <= 10);
+    }
+    // 4 + 5 bytes, "c" would exceed the budget
+    assert_eq!(cache.stats().entries, 2);
+    assert!(cache.get("d").is_some() && cache.get("e").is_some());
+
+    cache.insert("e", "y".repeat(2));
+    assert_eq!(cache.stats().bytes, 6);
+    assert_eq!(cache.get("e").as_deref(), Some("yy"));
+}
+
+#[test]
+fn test_oversize_media_is_not_kept_in_memory() {
+    let mut cache = MediaCache::new(4);
+    cache.insert("small", "ab".to_owned());
+    cache.insert("big", "abcde".to_owned());
+
+    assert_eq!(cache.get("big"), None);
+    assert_eq!(cache.get("small").as_deref(), Some("ab"));
+    assert_eq!(cache.stats().evictions, 0);
+
+    let dir = test_dir("media_cache_oversize");
+    let mut persisted = MediaCache::persisted(4, dir.clone()).unwrap();
+    persisted.insert("big", "abcde".to_owned());
+    assert_eq!(persisted.stats().entries, 0);
+    assert_eq!(persisted.get("big").as_deref(), Some("abcde"));
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_persisted_media_is_reloaded() {
+    let dir = test_dir("media_cache_reload");
+    let mut cache = MediaCache::persisted(1024, dir.clone()).unwrap();
+    cache.insert("../img/a.png", "aaaa".to_owned());
+    drop(cache);
+
+    let mut reloaded = MediaCache::persisted(1024, dir.clone()).unwrap();
+    assert_eq!(reloaded.stats().entries, 0);
+    assert_eq!(reloaded.get("../img/a.png").as_deref(), Some("aaaa"));
+    assert_eq!(reloaded.stats().entries, 1);
+    assert_eq!(reloaded.stats().hits, 1);
+    assert_eq!(reloaded.get("missing"), None);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    let _ = fs::remove_dir_all(dir);
+}
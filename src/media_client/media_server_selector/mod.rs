@@ -0,0 +1,245 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+
+use super::clock::{Clock, SystemClock};
+
+#[cfg(test)]
+mod test;
+
+/// `(source_id, file_id)` of the document that references the media
+type DocumentKey = (NodeId, String);
+
+/// how long a `GetMedia` can stay unanswered before it is counted as lost
+pub const PENDING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the media server for a `GetMedia` is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MediaServerStrategy {
+    /// every media server in turn
+    RoundRobin,
+    /// fewest hops, then lowest latency, then lowest loss
+    #[default]
+    Nearest,
+    /// fewest requests waiting for an answer, then lowest latency
+    LeastLoaded,
+    /// all the media of a document from the same server, chosen as `Nearest`
+    StickyPerDocument,
+    /// any reachable media server, drawn from the seeded generator of the client
+    Random,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaServerSelectionError {
+    /// no server answered `GetServerType` with `Media`
+    NoMediaServer,
+    /// there are media servers but no path to any of them
+    NoReachableMediaServer(Vec<NodeId>),
+}
+
+impl Display for MediaServerSelectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoMediaServer => write!(f, "no media server is known"),
+            Self::NoReachableMediaServer(servers) => {
+                write!(f, "none of the media servers {servers:?} is reachable")
+            }
+        }
+    }
+}
+
+/// A media server that can be chosen
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate {
+    pub id: NodeId,
+    pub hops: usize,
+}
+
+#[derive(Debug, Default)]
+struct ServerStats {
+    in_flight: usize,
+    received: u64,
+    lost: u64,
+    /// exponentially weighted moving average of the answer time
+    latency: Option<Duration>,
+}
+
+impl ServerStats {
+    fn loss_permille(&self) -> u64 {
+        self.lost * 1000 / (self.received + self.lost + 1)
+    }
+}
+
+pub struct MediaServerSelector {
+    strategy: MediaServerStrategy,
+    stats: HashMap<NodeId, ServerStats>,
+    /// `(server, media_id)` -> when the `GetMedia` was sent
+    pending: HashMap<(NodeId, String), Instant>,
+    round_robin: usize,
+    sticky: HashMap<DocumentKey, NodeId>,
+    clock: Arc<dyn Clock>,
+    rng: StdRng,
+}
+
+impl Default for MediaServerSelector {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock), StdRng::from_entropy())
+    }
+}
+
+impl MediaServerSelector {
+    pub fn new(clock: Arc<dyn Clock>, rng: StdRng) -> Self {
+        Self {
+            strategy: MediaServerStrategy::default(),
+            stats: HashMap::new(),
+            pending: HashMap::new(),
+            round_robin: 0,
+            sticky: HashMap::new(),
+            clock,
+            rng,
+        }
+    }
+    pub fn set_strategy(&mut self, strategy: MediaServerStrategy) {
+        self.strategy = strategy;
+    }
+    /// # Errors
+    /// `NoReachableMediaServer` if `candidates` is empty
+    pub fn select(
+        &mut self,
+        candidates: &[Candidate],
+        document: &DocumentKey,
+    ) -> Result<NodeId, MediaServerSelectionError> {
+        if candidates.is_empty() {
+            return Err(MediaServerSelectionError::NoReachableMediaServer(Vec::new()));
+        }
+        self.expire_pending();
+        let selected = match self.strategy {
+            MediaServerStrategy::RoundRobin => {
+                let ids = sorted_ids(candidates);
+                let selected = ids[self.round_robin % ids.len()];
+                self.round_robin = self.round_robin.wrapping_add(1);
+                selected
+            }
+            MediaServerStrategy::Random => {
+                let ids = sorted_ids(candidates);
+                ids[self.rng.gen_range(0..ids.len())]
+            }
+            MediaServerStrategy::Nearest => self.nearest(candidates),
+            MediaServerStrategy::LeastLoaded => candidates
+                .iter()
+                .min_by_key(|c| {
+                    let stats = self.stats.get(&c.id);
+                    (
+                        stats.map_or(0, |s| s.in_flight),
+                        stats.and_then(|s| s.latency).unwrap_or(Duration::MAX),
+                        c.id,
+                    )
+                })
+                .map_or(candidates[0].id, |c| c.id),
+            MediaServerStrategy::StickyPerDocument => match self.sticky.get(document) {
+                Some(server) if candidates.iter().any(|c| c.id == *server) => *server,
+                _ => {
+                    let server = self.nearest(candidates);
+                    self.sticky.insert(document.clone(), server);
+                    server
+                }
+            },
+        };
+        Ok(selected)
+    }
+    /// A `GetMedia` sent again before the answer is counted once, from the first request
+    pub fn request_sent(&mut self, server: NodeId, media_id: &str) {
+        let Entry::Vacant(entry) = self.pending.entry((server, media_id.to_owned())) else {
+            return;
+        };
+        entry.insert(self.clock.now());
+        self.stats.entry(server).or_default().in_flight += 1;
+    }
+    pub fn media_received(&mut self, server: NodeId, media_id: &str) {
+        let Some(sent_at) = self.pending.remove(&(server, media_id.to_owned())) else {
+            return;
+        };
+        let elapsed = self.clock.now().saturating_duration_since(sent_at);
+        let stats = self.stats.entry(server).or_default();
+        stats.in_flight = stats.in_flight.saturating_sub(1);
+        stats.received += 1;
+        stats.latency = Some(match stats.latency {
+            Some(latency) => (latency * 3 + elapsed) / 4,
+            None => elapsed,
+        });
+    }
+    /// Counts the `GetMedia` unanswered for `PENDING_TIMEOUT` as lost
+    pub fn expire_pending(&mut self) {
+        let now = self.clock.now();
+        let stats = &mut self.stats;
+        self.pending.retain(|(server, _), sent_at| {
+            if now.saturating_duration_since(*sent_at) < PENDING_TIMEOUT {
+                return true;
+            }
+            if let Some(stats) = stats.get_mut(server) {
+                stats.in_flight = stats.in_flight.saturating_sub(1);
+                stats.lost += 1;
+            }
+            false
+        });
+    }
+    /// Every media of the document arrived, the next one may use another server
+    pub fn document_completed(&mut self, document: &DocumentKey) {
+        self.sticky.remove(document);
+    }
+    /// Drops everything known about `server`, used when its directory entry expires or is removed
+    pub fn forget_server(&mut self, server: NodeId) {
+        self.stats.remove(&server);
+        self.pending
+            .retain(|(pending_server, _), _| *pending_server != server);
+        self.sticky
+            .retain(|_, sticky_server| *sticky_server != server);
+    }
+    /// Forgets the servers that are not in `servers`
+    pub fn retain_servers(&mut self, servers: &[NodeId]) {
+        let known = self
+            .stats
+            .keys()
+            .chain(self.sticky.values())
+            .copied()
+            .filter(|server| !servers.contains(server))
+            .collect::<Vec<NodeId>>();
+        for server in known {
+            self.forget_server(server);
+        }
+    }
+    /// Counts a fragment sent to `server` that had to be sent again
+    pub fn record_loss(&mut self, server: NodeId) {
+        if let Some(stats) = self.stats.get_mut(&server) {
+            stats.lost += 1;
+        }
+    }
+    fn nearest(&self, candidates: &[Candidate]) -> NodeId {
+        candidates
+            .iter()
+            .min_by_key(|c| {
+                let stats = self.stats.get(&c.id);
+                (
+                    c.hops,
+                    stats.and_then(|s| s.latency).unwrap_or(Duration::MAX),
+                    stats.map_or(0, ServerStats::loss_permille),
+                    c.id,
+                )
+            })
+            .map_or(candidates[0].id, |c| c.id)
+    }
+}
+
+/// the order of the candidates depends on how they were collected
+fn sorted_ids(candidates: &[Candidate]) -> Vec<NodeId> {
+    let mut ids = candidates.iter().map(|c| c.id).collect::<Vec<NodeId>>();
+    ids.sort_unstable();
+    ids
+}
@@ -0,0 +1,141 @@
+use super::*;
+use crate::media_client::ManualClock;
+
+#[test]
+fn test_random_media_server_depends_only_on_the_seed() {
+    let candidates = [7, 3, 9, 5].map(|id| Candidate { id, hops: 2 });
+    let document = (1, "index.html".to_owned());
+    let selections = |seed: u64| {
+        let mut selector = MediaServerSelector::new(
+            Arc::new(ManualClock::default()),
+            StdRng::seed_from_u64(seed),
+        );
+        selector.set_strategy(MediaServerStrategy::Random);
+        (0..32)
+            .map(|_| selector.select(&candidates, &document).unwrap())
+            .collect::<Vec<u8>>()
+    };
+
+    let first = selections(42);
+    assert_eq!(first, selections(42));
+    assert!(first.iter().all(|id| [3, 5, 7, 9].contains(id)));
+}
+
+fn selector(strategy: MediaServerStrategy) -> (Arc<ManualClock>, MediaServerSelector) {
+    let clock = Arc::new(ManualClock::default());
+    let mut selector = MediaServerSelector::new(clock.clone(), StdRng::seed_from_u64(0));
+    selector.set_strategy(strategy);
+    (clock, selector)
+}
+
+fn document(file_id: &str) -> DocumentKey {
+    (1, file_id.to_owned())
+}
+
+/// Answers `media_id` from `server` after `latency`
+fn answer(
+    clock: &ManualClock,
+    selector: &mut MediaServerSelector,
+    server: NodeId,
+    media_id: &str,
+    latency: Duration,
+) {
+    selector.request_sent(server, media_id);
+    clock.advance(latency);
+    selector.media_received(server, media_id);
+}
+
+#[test]
+fn test_round_robin_takes_every_server_in_turn() {
+    let (_clock, mut selector) = selector(MediaServerStrategy::RoundRobin);
+    let candidates = [9, 3, 5].map(|id| Candidate { id, hops: 1 });
+
+    let selected = (0..6)
+        .map(|_| selector.select(&candidates, &document("a")).unwrap())
+        .collect::<Vec<NodeId>>();
+    assert_eq!(selected, vec![3, 5, 9, 3, 5, 9]);
+    assert!(selector.select(&[], &document("a")).is_err());
+}
+
+#[test]
+fn test_nearest_prefers_hops_then_latency() {
+    let (clock, mut selector) = selector(MediaServerStrategy::Nearest);
+    let candidates = [
+        Candidate { id: 7, hops: 3 },
+        Candidate { id: 5, hops: 2 },
+        Candidate { id: 6, hops: 2 },
+    ];
+    assert_eq!(selector.select(&candidates, &document("a")), Ok(5));
+
+    answer(&clock, &mut selector, 5, "m1", Duration::from_millis(300));
+    answer(&clock, &mut selector, 6, "m2", Duration::from_millis(100));
+    answer(&clock, &mut selector, 7, "m3", Duration::from_millis(1));
+    assert_eq!(selector.select(&candidates, &document("a")), Ok(6));
+}
+
+#[test]
+fn test_least_loaded_prefers_fewest_requests_in_flight() {
+    let (_clock, mut selector) = selector(MediaServerStrategy::LeastLoaded);
+    let candidates = [5, 6].map(|id| Candidate { id, hops: 1 });
+
+    selector.request_sent(5, "m1");
+    assert_eq!(selector.select(&candidates, &document("a")), Ok(6));
+    selector.request_sent(6, "m2");
+    selector.request_sent(6, "m3");
+    assert_eq!(selector.select(&candidates, &document("a")), Ok(5));
+    selector.media_received(6, "m2");
+    selector.media_received(6, "m3");
+    assert_eq!(selector.select(&candidates, &document("a")), Ok(6));
+}
+
+#[test]
+fn test_sticky_per_document_keeps_the_first_server() {
+    let (_clock, mut selector) = selector(MediaServerStrategy::StickyPerDocument);
+    let near = Candidate { id: 5, hops: 1 };
+    let far = Candidate { id: 6, hops: 4 };
+
+    assert_eq!(selector.select(&[far], &document("a")), Ok(6));
+    assert_eq!(selector.select(&[near, far], &document("a")), Ok(6));
+    assert_eq!(selector.select(&[near, far], &document("b")), Ok(5));
+    // the server is not reachable anymore
+    assert_eq!(selector.select(&[near], &document("a")), Ok(5));
+    assert_eq!(selector.select(&[near, far], &document("a")), Ok(5));
+
+    selector.document_completed(&document("a"));
+    selector.document_completed(&document("b"));
+    assert!(selector.sticky.is_empty());
+}
+
+#[test]
+fn test_duplicate_and_lost_requests_leave_no_load() {
+    let (clock, mut selector) = selector(MediaServerStrategy::LeastLoaded);
+    selector.request_sent(5, "m1");
+    selector.request_sent(5, "m1");
+    selector.request_sent(5, "m2");
+    assert_eq!(selector.stats[&5].in_flight, 2);
+
+    selector.media_received(5, "m1");
+    assert_eq!(selector.stats[&5].in_flight, 1);
+    clock.advance(PENDING_TIMEOUT);
+    selector.expire_pending();
+    assert_eq!(selector.stats[&5].in_flight, 0);
+    assert_eq!(selector.stats[&5].lost, 1);
+    assert!(selector.pending.is_empty());
+}
+
+#[test]
+fn test_forgotten_servers_leave_nothing_behind() {
+    let (_clock, mut selector) = selector(MediaServerStrategy::StickyPerDocument);
+    let candidates = [5, 6].map(|id| Candidate { id, hops: 1 });
+    assert_eq!(selector.select(&candidates, &document("a")), Ok(5));
+    selector.request_sent(5, "m1");
+    selector.request_sent(6, "m2");
+
+    selector.retain_servers(&[6]);
+    assert!(!selector.stats.contains_key(&5));
+    assert!(selector.sticky.is_empty());
+    assert_eq!(selector.pending.len(), 1);
+
+    selector.forget_server(6);
+    assert!(selector.stats.is_empty() && selector.pending.is_empty());
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use wg_2024::{
+    network::NodeId,
+    packet::{NackType, Packet, PacketType},
+};
+
+#[cfg(test)]
+mod test;
+
+/// upper bounds of the `Histogram` buckets, in milliseconds
+pub const HISTOGRAM_BOUNDS_MS: [u64; 12] =
+    [1, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+/// `PacketType` without its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum PacketKind {
+    MsgFragment,
+    Ack,
+    Nack,
+    FloodRequest,
+    FloodResponse,
+}
+
+impl From<&PacketType> for PacketKind {
+    fn from(pack_type: &PacketType) -> Self {
+        match pack_type {
+            PacketType::MsgFragment(_) => Self::MsgFragment,
+            PacketType::Ack(_) => Self::Ack,
+            PacketType::Nack(_) => Self::Nack,
+            PacketType::FloodRequest(_) => Self::FloodRequest,
+            PacketType::FloodResponse(_) => Self::FloodResponse,
+        }
+    }
+}
+
+/// `NackType` without the node it names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum NackKind {
+    ErrorInRouting,
+    DestinationIsDrone,
+    Dropped,
+    UnexpectedRecipient,
+}
+
+impl From<&NackType> for NackKind {
+    fn from(nack_type: &NackType) -> Self {
+        match nack_type {
+            NackType::ErrorInRouting(_) => Self::ErrorInRouting,
+            NackType::DestinationIsDrone => Self::DestinationIsDrone,
+            NackType::Dropped => Self::Dropped,
+            NackType::UnexpectedRecipient(_) => Self::UnexpectedRecipient,
+        }
+    }
+}
+
+/// Durations counted in the buckets of `HISTOGRAM_BOUNDS_MS`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Histogram {
+    /// samples up to each bound, the last bucket counts the ones above every bound
+    pub buckets: [u64; HISTOGRAM_BOUNDS_MS.len() + 1],
+    pub count: u64,
+    pub sum_ms: u64,
+    pub max_ms: u64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, ms: u64) {
+        let bucket = HISTOGRAM_BOUNDS_MS.partition_point(|bound| *bound < ms);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum_ms = self.sum_ms.saturating_add(ms);
+        self.max_ms = self.max_ms.max(ms);
+    }
+    /// `None` if nothing was recorded
+    pub fn mean_ms(&self) -> Option<u64> {
+        self.sum_ms.checked_div(self.count)
+    }
+}
+
+/// What the `MediaClient` did since it started, sent with `MediaClientExtEvent::Metrics`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Metrics {
+    /// packets given to a neighbour or to the controller, duplicates included
+    pub packets_sent: BTreeMap<PacketKind, u64>,
+    pub packets_received: BTreeMap<PacketKind, u64>,
+    pub nacks_received: BTreeMap<NackKind, u64>,
+    /// fragments sent again after a `Nack`
+    pub retransmissions: u64,
+    /// `AskForFile` answered from the `TextCache`
+    pub text_cache_hits: u64,
+    /// media taken from the `MediaCache` instead of a media server
+    pub media_cache_hits: u64,
+    pub floods: u64,
+    /// content of the text files received from the servers
+    pub file_bytes: u64,
+    /// base64 content of the media received from the servers
+    pub media_bytes: u64,
+    /// time between a request and its answer, for each server
+    pub server_latency: BTreeMap<NodeId, Histogram>,
+    /// time between the text of a document and its last media
+    pub assembly_time: Histogram,
+}
+
+impl Metrics {
+    pub fn packet_sent(&mut self, packet: &Packet) {
+        *self
+            .packets_sent
+            .entry(PacketKind::from(&packet.pack_type))
+            .or_default() += 1;
+    }
+    pub fn packet_received(&mut self, packet: &Packet) {
+        *self
+            .packets_received
+            .entry(PacketKind::from(&packet.pack_type))
+            .or_default() += 1;
+        if let PacketType::Nack(nack) = &packet.pack_type {
+            *self
+                .nacks_received
+                .entry(NackKind::from(&nack.nack_type))
+                .or_default() += 1;
+        }
+    }
+    pub fn server_latency(&mut self, server_id: NodeId, ms: u64) {
+        self.server_latency.entry(server_id).or_default().record(ms);
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, thread, time::Duration};
+
+use crossbeam_channel::unbounded;
+use wg_2024::{network::SourceRoutingHeader, packet::Nack};
+
+use super::*;
+use crate::media_client::{
+    test_util::test_config, MediaClient, MediaClientConfig, MediaClientExtCommand,
+    MediaClientExtEvent,
+};
+
+fn nack(nack_type: NackType) -> Packet {
+    Packet::new_nack(
+        SourceRoutingHeader::new(vec![11, 1], 1),
+        3,
+        Nack {
+            fragment_index: 0,
+            nack_type,
+        },
+    )
+}
+
+#[test]
+fn test_histogram_buckets() {
+    let mut histogram = Histogram::default();
+    assert_eq!(histogram.mean_ms(), None);
+
+    for ms in [0, 1, 2, 10, 10_000, 60_000] {
+        histogram.record(ms);
+    }
+    assert_eq!(histogram.buckets[0], 2);
+    assert_eq!(histogram.buckets[1], 1);
+    assert_eq!(histogram.buckets[2], 1);
+    assert_eq!(histogram.buckets[HISTOGRAM_BOUNDS_MS.len() - 1], 1);
+    assert_eq!(histogram.buckets[HISTOGRAM_BOUNDS_MS.len()], 1);
+    assert_eq!(histogram.count, 6);
+    assert_eq!(histogram.max_ms, 60_000);
+    assert_eq!(histogram.mean_ms(), Some(70_013 / 6));
+}
+
+#[test]
+fn test_client_counts_packets_and_nacks() {
+    let (drone_send, drone_recv) = unbounded();
+    let (ext_send, ext_recv) = unbounded();
+    let mut client = MediaClient::new(
+        1,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(11, drone_send)]),
+        test_config("metrics_counts"),
+    )
+    .with_ext_controller(ext_send, unbounded().1);
+
+    client.flood_network();
+    client.handle_packet(nack(NackType::Dropped));
+    client.handle_packet(nack(NackType::ErrorInRouting(12)));
+    client.handle_packet(nack(NackType::Dropped));
+    client.handle_ext_command(MediaClientExtCommand::GetMetrics);
+    drop(client);
+
+    let Some(MediaClientExtEvent::Metrics(metrics)) = ext_recv
+        .try_iter()
+        .find(|event| matches!(event, MediaClientExtEvent::Metrics(_)))
+    else {
+        panic!("no metrics");
+    };
+    assert_eq!(metrics.floods, 1);
+    assert_eq!(
+        metrics.packets_sent.get(&PacketKind::FloodRequest).copied(),
+        Some(drone_recv.try_iter().count() as u64)
+    );
+    assert_eq!(metrics.packets_received.get(&PacketKind::Nack), Some(&3));
+    assert_eq!(
+        metrics.nacks_received,
+        BTreeMap::from([(NackKind::ErrorInRouting, 1), (NackKind::Dropped, 2)])
+    );
+    assert_eq!(metrics.retransmissions, 0);
+}
+
+#[test]
+fn test_metrics_are_sent_periodically() {
+    let (controller_send, controller_recv) = unbounded();
+    let (ext_send, ext_recv) = unbounded();
+    let (drone_send, _drone_recv) = unbounded();
+    // a disconnected packet channel would always win the `select_biased`
+    let (_packet_send, packet_recv) = unbounded();
+    let mut client = MediaClient::new(
+        1,
+        unbounded().0,
+        controller_recv,
+        packet_recv,
+        HashMap::from([(11, drone_send)]),
+        MediaClientConfig {
+            metrics_interval: Some(Duration::from_millis(20)),
+            ..test_config("metrics_periodic")
+        },
+    )
+    .with_ext_controller(ext_send, unbounded().1);
+    let handle = thread::spawn(move || client.run());
+
+    for _ in 0..2 {
+        let event = ext_recv.recv_timeout(Duration::from_secs(5)).unwrap();
+        let MediaClientExtEvent::Metrics(metrics) = event else {
+            panic!("unexpected event {event:?}");
+        };
+        assert_eq!(metrics.floods, 1);
+    }
+    drop(controller_send);
+    handle.join().unwrap();
+}
@@ -1,7 +1,8 @@
 use std::{
+    cell::RefCell,
     collections::{HashMap, HashSet},
-    thread,
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use assembler::HighLevelMessageFactory;
@@ -10,32 +11,111 @@ use messages::client_commands::{MediaClientCommand, MediaClientEvent};
 use packet_cache::PacketCache;
 use source_routing::Router;
 
-use crossbeam_channel::{select_biased, Receiver, Sender};
+use crossbeam_channel::{after, never, select_biased, Receiver, Sender};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use wg_2024::{
     network::NodeId,
     packet::{NodeType, Packet},
 };
 
+mod config;
+mod ext_commands;
 mod handle_command;
 mod handle_message;
 mod handle_packet;
 mod send_to;
 
+mod clock;
+mod faults;
 mod file_assembler;
+mod file_catalogue;
+mod html_rewrite;
+mod manifest;
+mod media_cache;
+mod media_server_selector;
+mod metrics;
+mod renderer;
+mod retention;
+mod sanitize;
+mod server_directory;
+mod text_cache;
+mod topology;
+mod trace;
+
+#[cfg(test)]
+pub(crate) mod test_util;
+
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use config::{MediaClientConfig, RetentionLimits, SubdirectoryScheme, ViewerLaunch};
+pub use ext_commands::{MediaClientExtCommand, MediaClientExtEvent};
+pub use faults::{FaultConfig, FaultCounts};
+pub use file_assembler::{AssemblerReport, NameKind, SizeMismatch, SizeMismatchPolicy};
+pub use file_catalogue::{CatalogueEntry, FilesListDiff};
+pub use manifest::{BundleManifest, FetchInfo, MediaManifest, MANIFEST_FILE};
+pub use media_cache::{MediaCache, MediaCacheStats, DEFAULT_MEDIA_CACHE_BUDGET, MEDIA_CACHE_DIR};
+pub use media_server_selector::{MediaServerSelectionError, MediaServerStrategy, PENDING_TIMEOUT};
+pub use metrics::{Histogram, Metrics, NackKind, PacketKind, HISTOGRAM_BOUNDS_MS};
+pub use renderer::{DocumentUpdate, OutputMode};
+pub use sanitize::{NameRejection, MAX_NAME_LEN};
+pub use text_cache::{CachedTextFile, TextCache, TextCachePolicy, TEXT_CACHE_DIR};
+pub use topology::{NodeInfo, TopologySnapshot};
+pub use trace::{
+    read_trace, replay, Divergence, RecordedCommand, ReplayReport, SharedBuffer, TraceEntry,
+    TraceRecord, TraceRecorder,
+};
+
+use faults::FaultInjector;
+use file_catalogue::FileCatalogue;
+use media_server_selector::MediaServerSelector;
+use renderer::Renderer;
+use server_directory::{ServerDirectory, DEFAULT_SERVER_TYPE_TTL};
+use topology::Topology;
 
 pub struct MediaClient {
     id: NodeId,
+    clock: Arc<dyn Clock>,
 
     router: Router,
+    topology: Topology,
     message_factory: HighLevelMessageFactory,
 
     packet_cache: PacketCache,
+    /// fragments of each session sent by the client and not acked yet
+    unacked_fragments: HashMap<u64, HashSet<u64>>,
+    /// last route announced with `RouteChanged` for each rerouted session
+    session_routes: HashMap<u64, Vec<NodeId>>,
     file_assembler: FileAssembler,
-    media_server: HashSet<NodeId>,
+    renderer: Renderer,
+    size_mismatch_policy: SizeMismatchPolicy,
+    /// `(source_id, file_id)` -> `GetFile` sent again because of a `SizeMismatch`
+    size_refetches: HashMap<(NodeId, String), u8>,
+    /// media asked again because they did not match the `integrity` of a document
+    integrity_refetches: HashSet<String>,
+    file_catalogue: FileCatalogue,
+    media_cache: MediaCache,
+    text_cache: TextCache,
+    server_directory: ServerDirectory,
+    media_selector: MediaServerSelector,
+    /// `((source_id, file_id), media_id)` waiting for a media server to be known
+    unfetched_media: Vec<((NodeId, String), String)>,
+    /// when each `GetFile` (`(Some(server), file_id)`) and `GetMedia` (`(None, media_id)`)
+    /// waiting for an answer was sent, in milliseconds since `UNIX_EPOCH`
+    requested_at: HashMap<(Option<NodeId>, String), u64>,
+    trace: Option<TraceRecorder>,
+    /// seed of the random choices, written in the trace so that `replay` makes the same
+    seed: u64,
+    faults: FaultInjector,
+    metrics: RefCell<Metrics>,
+    metrics_interval: Option<Duration>,
+    /// when the `Metrics` are sent next, `None` if they are only sent on `GetMetrics`
+    next_metrics: Option<Instant>,
 
     controller_send: Sender<MediaClientEvent>,
     controller_recv: Receiver<MediaClientCommand>,
 
+    ext_controller_send: Option<Sender<MediaClientExtEvent>>,
+    ext_controller_recv: Receiver<MediaClientExtCommand>,
+
     packet_recv: Receiver<Packet>,
     packet_send: HashMap<NodeId, Sender<Packet>>,
 }
@@ -49,48 +129,148 @@ impl MediaClient {
         controller_recv: Receiver<MediaClientCommand>,
         packet_recv: Receiver<Packet>,
         packet_send: HashMap<NodeId, Sender<Packet>>,
+        config: MediaClientConfig,
     ) -> Self {
+        let output_dir = config.output_dir(id);
+        let clock = config.clock.clone();
+        let seed = config.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
         Self {
             id,
             router: Router::new(id, NodeType::Client),
+            topology: Topology::new(id),
             message_factory: HighLevelMessageFactory::new(id, NodeType::Client),
             packet_cache: PacketCache::new(),
-            file_assembler: FileAssembler::new(),
-            media_server: HashSet::new(),
+            unacked_fragments: HashMap::new(),
+            session_routes: HashMap::new(),
+            file_assembler: FileAssembler::new().with_partial_files(config.progressive),
+            renderer: Renderer::new(output_dir.clone(), &config),
+            size_mismatch_policy: SizeMismatchPolicy::default(),
+            size_refetches: HashMap::new(),
+            integrity_refetches: HashSet::new(),
+            file_catalogue: FileCatalogue::default(),
+            media_cache: MediaCache::default(),
+            text_cache: TextCache::new(output_dir.join(TEXT_CACHE_DIR))
+                .with_policy(config.text_cache_policy)
+                .with_clock(clock.clone()),
+            server_directory: ServerDirectory::new(DEFAULT_SERVER_TYPE_TTL, clock.clone()),
+            seed,
+            faults: FaultInjector::new(StdRng::seed_from_u64(rng.gen())),
+            media_selector: MediaServerSelector::new(clock.clone(), rng),
+            unfetched_media: Vec::new(),
+            requested_at: HashMap::new(),
+            trace: None,
+            metrics: RefCell::new(Metrics::default()),
+            metrics_interval: config.metrics_interval,
+            next_metrics: config
+                .metrics_interval
+                .map(|interval| clock.now() + interval),
+            clock,
             controller_send,
             controller_recv,
+            ext_controller_send: None,
+            ext_controller_recv: never(),
             packet_recv,
             packet_send,
         }
     }
+    /// Connects the channels used for `MediaClientExtCommand` and `MediaClientExtEvent`
+    #[must_use]
+    pub fn with_ext_controller(
+        mut self,
+        ext_controller_send: Sender<MediaClientExtEvent>,
+        ext_controller_recv: Receiver<MediaClientExtCommand>,
+    ) -> Self {
+        self.ext_controller_send = Some(ext_controller_send);
+        self.ext_controller_recv = ext_controller_recv;
+        self
+    }
+    /// Replaces the in-memory `MediaCache` used by default, a persisted one
+    /// usually goes in `{output_dir}/.media_cache` (`MEDIA_CACHE_DIR`)
+    #[must_use]
+    pub fn with_media_cache(mut self, media_cache: MediaCache) -> Self {
+        self.media_cache = media_cache;
+        self
+    }
+    /// Replaces the `TextCache` in `{output_dir}/.text_cache` used by default
+    #[must_use]
+    pub fn with_text_cache(mut self, text_cache: TextCache) -> Self {
+        self.text_cache = text_cache;
+        self
+    }
+    /// Records every packet, command and event, see `replay`
+    #[must_use]
+    pub fn with_trace_recorder(mut self, recorder: TraceRecorder) -> Self {
+        self.trace = Some(recorder.with_clock(self.clock.clone()));
+        self
+    }
 }
 
 impl MediaClient {
     //methods
+    /// Returns when the controller channel is disconnected
     pub fn run(&mut self) {
+        self.start_trace();
         self.flood_network();
-        thread::sleep(Duration::from_secs(3));
+        self.clock.sleep(Duration::from_secs(3));
         loop {
+            let next_ack = self.faults.next_due();
+            let delayed_acks = next_ack.map_or_else(never, |due| self.timer(due));
+            let metrics_due = self.next_metrics.map_or_else(never, |due| self.timer(due));
+            let give_up_due = self
+                .renderer
+                .next_give_up()
+                .map_or_else(never, |due| self.timer(due));
             select_biased! {
                 recv(self.controller_recv) -> command => {
-                    if let Ok(command) = command {
-                        self.handle_command(command) ;
+                    match command {
+                        Ok(command) => self.handle_command(command),
+                        Err(_) => break,
+                    }
+                } ,
+                recv(self.ext_controller_recv) -> command => {
+                    match command {
+                        Ok(command) => self.handle_ext_command(command),
+                        Err(_) => self.ext_controller_recv = never(),
                     }
                 } ,
                 recv(self.packet_recv) -> packet => {
                     if let Ok(packet) = packet {
                         self.handle_packet(packet) ;
                     }
-                }
+                } ,
+                recv(delayed_acks) -> _ => {
+                    if let Some(due) = next_ack {
+                        self.send_delayed_acks(due);
+                    }
+                } ,
+                recv(metrics_due) -> _ => self.send_periodic_metrics(),
+                recv(give_up_due) -> _ => self.give_up_partial_documents(),
             }
         }
     }
+    /// Fires when `clock` reaches `due`
+    fn timer(&self, due: Instant) -> Receiver<Instant> {
+        after(due.saturating_duration_since(self.clock.now()))
+    }
+    /// The next emission is scheduled from the previous one, so that a `ManualClock`
+    /// that is not advanced does not emit continuously
+    fn send_periodic_metrics(&mut self) {
+        let (Some(due), Some(interval)) = (self.next_metrics, self.metrics_interval) else {
+            return;
+        };
+        self.next_metrics = Some(due.max(self.clock.now()) + interval);
+        self.send_ext_controller(MediaClientExtEvent::Metrics(self.metrics.borrow().clone()));
+    }
     fn flood_network(&mut self) {
+        self.metrics.get_mut().floods += 1;
         let requests = self.router.get_flood_requests(self.packet_send.len());
-        for (sender, request) in self.packet_send.values().zip(requests) {
+        let mut neighbours = self.packet_send.iter().collect::<Vec<_>>();
+        neighbours.sort_unstable_by_key(|(id, _)| **id);
+        for ((_, sender), request) in neighbours.into_iter().zip(requests) {
             self.send_packet(request, Some(sender));
         }
-        thread::sleep(Duration::from_secs(2));
+        self.clock.sleep(Duration::from_secs(2));
     }
     // fn get_discovered_server(&self, id: NodeId) -> Option<&DiscoveredServer> {
     //     let index = self.discovered_servers.iter().position(|s| s.id == id)?;
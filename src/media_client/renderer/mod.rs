@@ -0,0 +1,455 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Cursor, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose, Engine};
+use image::{codecs::jpeg::JpegDecoder, DynamicImage};
+use log::error;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+
+use super::{
+    clock::Clock,
+    config::{MediaClientConfig, SubdirectoryScheme, ViewerLaunch},
+    file_assembler::AddedFileReturn,
+    html_rewrite::{rewrite_img_src, with_refresh},
+    manifest::{BundleManifest, MANIFEST_FILE},
+    retention::RetentionManager,
+    sanitize::sanitize_component,
+};
+
+#[cfg(test)]
+mod test;
+
+/// mime type used when the media format cannot be recognised
+const FALLBACK_MIME_TYPE: &str = "image/jpeg";
+/// transparent 1x1 gif shown in place of the media that did not arrive yet
+const PLACEHOLDER_SRC: &str =
+    "data:image/gif;base64,R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7";
+/// crossed box shown in place of the media given up, see `GIVE_UP_AFTER`
+const BROKEN_SRC: &str = "data:image/svg+xml;base64,\
+    PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciIHdpZHRoPSIxNiIgaGVpZ2h0PSIx\
+    NiI+PHBhdGggZD0iTTEgMWgxNHYxNEgxek0xIDFsMTQgMTRNMTUgMUwxIDE1IiBmaWxsPSJub25lIiBz\
+    dHJva2U9ImdyYXkiLz48L3N2Zz4=";
+/// how often a partially received document reloads itself
+const REFRESH_SECONDS: u32 = 1;
+/// how long a partially received document reloads itself before the missing media
+/// are shown as broken and the refresh is removed
+pub const GIVE_UP_AFTER: Duration = Duration::from_secs(60);
+
+/// How a completed document is written to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum OutputMode {
+    /// `{output_dir}/{source}_{file}_{time}/` with the html, every media and a `manifest.json`
+    #[default]
+    Bundle,
+    /// `{output_dir}/{source}_{file}_{time}.html` with the media inlined as `data:` URIs
+    SingleHtml,
+}
+
+/// Sent every time a document is written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentUpdate {
+    pub source_id: NodeId,
+    pub file_id: String,
+    /// the html to show
+    pub path: PathBuf,
+    pub media_received: usize,
+    pub media_total: usize,
+    /// `false` if some media are missing, the document reloads itself until they arrive
+    /// or until it is given up
+    pub complete: bool,
+}
+
+/// A document written before all its media arrived
+struct InProgress {
+    output_path: PathBuf,
+    /// `(media_id, local name)` already saved in the bundle
+    written: HashSet<(String, String)>,
+    /// when the first partial render was written
+    started: Instant,
+    /// the missing media are no longer waited for
+    given_up: bool,
+    /// the `OutputMode` it was started with, kept until it is complete
+    mode: OutputMode,
+}
+
+/// Writes completed documents to disk and opens them
+pub struct Renderer {
+    output_dir: PathBuf,
+    mode: OutputMode,
+    viewer: ViewerLaunch,
+    retention: RetentionManager,
+    in_progress: HashMap<(NodeId, String), InProgress>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        let config = MediaClientConfig::default();
+        Self::new(config.output_root.clone(), &config)
+    }
+}
+
+impl Renderer {
+    /// Renderer writing in `output_dir`, the rest is taken from `config`
+    pub fn new(output_dir: PathBuf, config: &MediaClientConfig) -> Self {
+        let retention = RetentionManager::new(output_dir.clone(), config.retention)
+            .with_clock(config.clock.clone());
+        Self {
+            retention: match config.subdirectory {
+                SubdirectoryScheme::Shared => retention.shared(),
+                SubdirectoryScheme::PerClient => retention,
+            },
+            output_dir,
+            mode: config.output_mode,
+            viewer: config.viewer.clone(),
+            in_progress: HashMap::new(),
+            clock: config.clock.clone(),
+        }
+    }
+    /// The documents in progress keep the mode they were started with
+    pub fn set_mode(&mut self, mode: OutputMode) {
+        self.mode = mode;
+    }
+    /// Removes every document written in the output directory
+    ///
+    /// # Returns
+    /// the number of documents removed
+    pub fn purge_output(&mut self) -> usize {
+        self.in_progress.clear();
+        self.retention.purge()
+    }
+    /// When the next document still reloading itself has to be given up
+    pub fn next_give_up(&self) -> Option<Instant> {
+        self.in_progress
+            .values()
+            .filter(|in_progress| !in_progress.given_up)
+            .map(|in_progress| in_progress.started + GIVE_UP_AFTER)
+            .min()
+    }
+    /// Stops waiting for the documents reloading themselves for `GIVE_UP_AFTER`,
+    /// their next `PartialFile` is written without refresh
+    ///
+    /// # Returns
+    /// `(source_id, file_id)` of each one
+    pub fn give_up(&mut self, now: Instant) -> Vec<(NodeId, String)> {
+        self.in_progress
+            .iter_mut()
+            .filter(|(_, in_progress)| {
+                !in_progress.given_up && in_progress.started + GIVE_UP_AFTER <= now
+            })
+            .map(|(key, in_progress)| {
+                in_progress.given_up = true;
+                key.clone()
+            })
+            .collect()
+    }
+    /// # Returns
+    /// where the document was written, `None` if it was not
+    pub fn render(&mut self, file: AddedFileReturn) -> Option<DocumentUpdate> {
+        match file {
+            AddedFileReturn::CompleteFile {
+                source_id,
+                file_id,
+                content,
+                media_content,
+                local_names,
+                manifest,
+            } => self.render_complete(
+                source_id,
+                file_id,
+                &content,
+                &media_content,
+                &local_names,
+                &manifest,
+            ),
+            AddedFileReturn::PartialFile {
+                source_id,
+                file_id,
+                content,
+                media_content,
+                local_names,
+            } => self.render_partial(source_id, file_id, &content, &media_content, &local_names),
+            AddedFileReturn::RefToMedia(_) => None,
+        }
+    }
+    fn render_complete(
+        &mut self,
+        source_id: NodeId,
+        file_id: String,
+        content: &str,
+        media_content: &HashMap<String, String>,
+        local_names: &HashMap<String, String>,
+        manifest: &BundleManifest,
+    ) -> Option<DocumentUpdate> {
+        let safe_id = sanitize_component(&file_id).ok()?;
+        let key = (source_id, file_id);
+        let in_progress = self.in_progress.remove(&key);
+        let opened = in_progress.is_some();
+        let (output_path, mut written, mode) = match in_progress {
+            Some(in_progress) => (
+                in_progress.output_path,
+                in_progress.written,
+                in_progress.mode,
+            ),
+            None => (
+                self.new_output_path(source_id, &safe_id, self.mode),
+                HashSet::new(),
+                self.mode,
+            ),
+        };
+        let html_path = match mode {
+            OutputMode::Bundle => {
+                remove_stale_media(&output_path, &written, local_names);
+                write_bundle(
+                    &output_path,
+                    &safe_id,
+                    content,
+                    media_content,
+                    local_names,
+                    &mut written,
+                )?;
+                write_manifest(&output_path, manifest);
+                self.retention.add(&output_path)
+            }
+            OutputMode::SingleHtml => {
+                write_single_html(&output_path, content, media_content, local_names)?;
+                self.retention.add(&output_path)
+            }
+        };
+        self.retention.enforce(&html_path);
+        let path = match mode {
+            OutputMode::Bundle => html_path.join(bundle_html_name(&safe_id)),
+            OutputMode::SingleHtml => html_path,
+        };
+        if !opened && path.exists() {
+            self.launch_viewer(&path);
+        }
+        Some(DocumentUpdate {
+            source_id,
+            file_id: key.1,
+            path,
+            media_received: media_content.len(),
+            media_total: local_names.len(),
+            complete: true,
+        })
+    }
+    /// Writes the document with a placeholder for every media that did not arrive yet
+    /// and makes it reload itself until `render_complete` replaces it or it is given up
+    fn render_partial(
+        &mut self,
+        source_id: NodeId,
+        file_id: String,
+        content: &str,
+        media_content: &HashMap<String, String>,
+        local_names: &HashMap<String, String>,
+    ) -> Option<DocumentUpdate> {
+        let safe_id = sanitize_component(&file_id).ok()?;
+        let key = (source_id, file_id);
+        let opened = self.in_progress.contains_key(&key);
+        if !opened {
+            let output_path = self.new_output_path(source_id, &safe_id, self.mode);
+            self.in_progress.insert(
+                key.clone(),
+                InProgress {
+                    output_path,
+                    written: HashSet::new(),
+                    started: self.clock.now(),
+                    given_up: false,
+                    mode: self.mode,
+                },
+            );
+        }
+        let in_progress = self.in_progress.get_mut(&key)?;
+        let missing = local_names
+            .iter()
+            .filter(|(media_id, _)| !media_content.contains_key(*media_id))
+            .map(|(_, local_name)| local_name.as_str())
+            .collect::<HashSet<&str>>();
+        let placeholder = if in_progress.given_up {
+            BROKEN_SRC
+        } else {
+            PLACEHOLDER_SRC
+        };
+        let content = rewrite_img_src(content, |src| {
+            missing.contains(src).then(|| placeholder.to_owned())
+        });
+        let content = if in_progress.given_up {
+            content
+        } else {
+            with_refresh(&content, REFRESH_SECONDS)
+        };
+        let path = match in_progress.mode {
+            OutputMode::Bundle => write_bundle(
+                &in_progress.output_path,
+                &safe_id,
+                &content,
+                media_content,
+                local_names,
+                &mut in_progress.written,
+            )?,
+            OutputMode::SingleHtml => write_single_html(
+                &in_progress.output_path,
+                &content,
+                media_content,
+                local_names,
+            )?,
+        };
+        if !opened {
+            self.launch_viewer(&path);
+        }
+        Some(DocumentUpdate {
+            source_id,
+            file_id: key.1,
+            path,
+            media_received: media_content.len(),
+            media_total: local_names.len(),
+            complete: false,
+        })
+    }
+    /// `{source}_{file}_{time}`, as a directory or an html depending on the `OutputMode`
+    fn new_output_path(&self, source_id: NodeId, safe_id: &str, mode: OutputMode) -> PathBuf {
+        let istant = self.clock.unix_secs();
+        let name = format!("{source_id}_{safe_id}_{istant}");
+        match mode {
+            OutputMode::Bundle => self.output_dir.join(name),
+            OutputMode::SingleHtml => self.output_dir.join(format!("{name}.html")),
+        }
+    }
+    fn launch_viewer(&self, file_path: &Path) {
+        match &self.viewer {
+            ViewerLaunch::None => {}
+            ViewerLaunch::SystemBrowser => {
+                let _ = webbrowser::open(file_path.to_str().unwrap_or_default());
+            }
+            ViewerLaunch::Command { program, args } => {
+                let _ = Command::new(program)
+                    .args(args)
+                    .arg(file_path)
+                    .spawn()
+                    .inspect_err(|e| {
+                        error!("[mediaclient] error launching {program}: {e}");
+                    });
+            }
+        }
+    }
+}
+
+/// name of the html inside a bundle
+fn bundle_html_name(safe_id: &str) -> PathBuf {
+    Path::new(safe_id).with_extension("html")
+}
+
+/// Writes the html and the media that are not in `written` yet
+///
+/// # Returns
+/// the path of the html, `None` if it could not be written
+fn write_bundle(
+    dir_path: &Path,
+    safe_id: &str,
+    content: &str,
+    media_content: &HashMap<String, String>,
+    local_names: &HashMap<String, String>,
+    written: &mut HashSet<(String, String)>,
+) -> Option<PathBuf> {
+    let _ = fs::create_dir_all(dir_path).inspect_err(|e| {
+        error!("[mediaclient] error creating dir {e}");
+    });
+    let file_path = dir_path.join(bundle_html_name(safe_id));
+
+    let mut text_file = File::create(&file_path)
+        .inspect_err(|e| {
+            error!("[mediaclient] error creating textfile {e}");
+        })
+        .ok()?;
+    let _ = write!(text_file, "{content}");
+    let _ = text_file.flush();
+    for (media_id, m_content) in media_content {
+        let Some(local_name) = local_names.get(media_id) else {
+            continue;
+        };
+        if !written.insert((media_id.clone(), local_name.clone())) {
+            continue;
+        }
+        if let Some(image) = get_dynimage_from_string(m_content) {
+            let _ = image.save(dir_path.join(local_name)).inspect_err(|e| {
+                error!("[mediaclient] error creating mediaFile {e}");
+            });
+        }
+    }
+    Some(file_path)
+}
+
+fn write_manifest(dir_path: &Path, manifest: &BundleManifest) {
+    let result = serde_json::to_string_pretty(manifest)
+        .map_err(io::Error::from)
+        .and_then(|json| fs::write(dir_path.join(MANIFEST_FILE), json));
+    let _ = result.inspect_err(|e| {
+        error!("[mediaclient] error creating manifest {e}");
+    });
+}
+
+/// Removes the media saved by a partial render under a name the complete document does not use
+fn remove_stale_media(
+    dir_path: &Path,
+    written: &HashSet<(String, String)>,
+    local_names: &HashMap<String, String>,
+) {
+    for (_, local_name) in written {
+        if !local_names.values().any(|name| name == local_name) {
+            let _ = fs::remove_file(dir_path.join(local_name));
+        }
+    }
+}
+
+/// # Returns
+/// `file_path`, `None` if it could not be written
+fn write_single_html(
+    file_path: &Path,
+    content: &str,
+    media_content: &HashMap<String, String>,
+    local_names: &HashMap<String, String>,
+) -> Option<PathBuf> {
+    if let Some(parent) = file_path.parent() {
+        let _ = fs::create_dir_all(parent).inspect_err(|e| {
+            error!("[mediaclient] error creating dir {e}");
+        });
+    }
+    let data_uris = local_names
+        .iter()
+        .filter_map(|(media_id, local_name)| {
+            Some((local_name.as_str(), data_uri(media_content.get(media_id)?)))
+        })
+        .collect::<HashMap<&str, String>>();
+    let content = rewrite_img_src(content, |src| data_uris.get(src).cloned());
+    fs::write(file_path, content)
+        .inspect_err(|e| {
+            error!("[mediaclient] error creating textfile {e}");
+        })
+        .ok()?;
+    Some(file_path.to_path_buf())
+}
+
+/// `data:` URI of a base64 media, with the mime type guessed from its content
+fn data_uri(base_64: &str) -> String {
+    let mime_type = general_purpose::STANDARD
+        .decode(base_64)
+        .ok()
+        .and_then(|content| image::guess_format(&content).ok())
+        .map_or(FALLBACK_MIME_TYPE, |format| format.to_mime_type());
+    format!("data:{mime_type};base64,{base_64}")
+}
+
+fn get_dynimage_from_string(base_64: &str) -> Option<DynamicImage> {
+    let file_media_content = general_purpose::STANDARD.decode(base_64).ok()?;
+    let cursor = Cursor::new(file_media_content);
+    let decoder = JpegDecoder::new(cursor).ok()?;
+    DynamicImage::from_decoder(decoder).ok()
+}
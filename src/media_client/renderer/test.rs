@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use super::*;
+use crate::media_client::{
+    file_assembler::FileAssembler, manifest::FetchInfo, test_util::test_config, ManualClock,
+};
+
+fn partial(media_content: HashMap<String, String>) -> AddedFileReturn {
+    AddedFileReturn::PartialFile {
+        source_id: 21,
+        file_id: "a.html".to_owned(),
+        content: "<html><head></head><img src=\"logo.jpg\"></html>".to_owned(),
+        media_content,
+        local_names: HashMap::from([("images/logo.jpg".to_owned(), "logo.jpg".to_owned())]),
+    }
+}
+
+/// Renderer writing in `test_dir(name)`
+fn renderer(name: &str, config: MediaClientConfig) -> (Renderer, PathBuf) {
+    let config = MediaClientConfig {
+        output_root: test_config(name).output_root,
+        ..config
+    };
+    (
+        Renderer::new(config.output_root.clone(), &config),
+        config.output_root,
+    )
+}
+
+fn image_base64(format: image::ImageFormat) -> String {
+    let mut encoded = Cursor::new(Vec::new());
+    DynamicImage::new_rgb8(4, 4)
+        .write_to(&mut encoded, format)
+        .unwrap();
+    general_purpose::STANDARD.encode(encoded.into_inner())
+}
+
+#[test]
+fn test_partial_document_stops_reloading_once_given_up() {
+    let clock = Arc::new(ManualClock::default());
+    let (mut renderer, dir) = renderer(
+        "renderer_give_up",
+        MediaClientConfig {
+            output_mode: OutputMode::SingleHtml,
+            clock: clock.clone(),
+            ..test_config("renderer_give_up")
+        },
+    );
+    let start = clock.now();
+
+    let update = renderer.render(partial(HashMap::new())).unwrap();
+    let html = fs::read_to_string(&update.path).unwrap();
+    assert!(html.contains("http-equiv=\"refresh\""));
+    assert!(html.contains(PLACEHOLDER_SRC));
+    assert_eq!(renderer.next_give_up(), Some(start + GIVE_UP_AFTER));
+
+    clock.advance(GIVE_UP_AFTER - Duration::from_millis(1));
+    assert!(renderer.give_up(clock.now()).is_empty());
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(
+        renderer.give_up(clock.now()),
+        vec![(21, "a.html".to_owned())]
+    );
+    assert_eq!(renderer.next_give_up(), None);
+
+    let final_update = renderer.render(partial(HashMap::new())).unwrap();
+    assert_eq!(final_update.path, update.path);
+    assert!(!final_update.complete);
+    let html = fs::read_to_string(&final_update.path).unwrap();
+    assert!(!html.contains("http-equiv=\"refresh\""));
+    assert!(html.contains(BROKEN_SRC));
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_document_in_progress_keeps_its_output_mode() {
+    for (first, then) in [
+        (OutputMode::Bundle, OutputMode::SingleHtml),
+        (OutputMode::SingleHtml, OutputMode::Bundle),
+    ] {
+        let name = format!("renderer_mode_{first:?}");
+        let (mut renderer, dir) = renderer(
+            &name,
+            MediaClientConfig {
+                output_mode: first,
+                ..test_config(&name)
+            },
+        );
+        let mut assembler = FileAssembler::new().with_partial_files(true);
+        let html = "<html><img src=\"logo.jpg\"></html>";
+        let assembled = assembler.add_textfile(
+            21,
+            "a.html",
+            html.to_owned(),
+            html.len(),
+            FetchInfo::default(),
+        );
+        let partial = assembled
+            .into_iter()
+            .find_map(|file| renderer.render(file))
+            .unwrap();
+
+        renderer.set_mode(then);
+        let media = general_purpose::STANDARD.encode("logo");
+        let assembled = assembler.add_media_file("logo.jpg", media, FetchInfo::default());
+        let complete = assembled
+            .into_iter()
+            .find_map(|file| renderer.render(file))
+            .unwrap();
+        assert!(complete.complete);
+        assert_eq!(complete.path, partial.path);
+        assert!(complete.path.is_file());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let bundle = complete.path.parent().unwrap();
+        assert_eq!(
+            bundle.join(MANIFEST_FILE).exists(),
+            first == OutputMode::Bundle
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+#[test]
+fn test_single_html_embeds_every_media_as_a_data_uri() {
+    let (mut renderer, dir) = renderer(
+        "renderer_single_html",
+        MediaClientConfig {
+            output_mode: OutputMode::SingleHtml,
+            ..test_config("renderer_single_html")
+        },
+    );
+    let mut assembler = FileAssembler::new();
+    let html = "<html><img src=\"logo.jpg\"><img src=\"icons/dot.png\"></html>";
+    assembler.add_textfile(
+        21,
+        "a.html",
+        html.to_owned(),
+        html.len(),
+        FetchInfo::default(),
+    );
+    let jpeg = image_base64(image::ImageFormat::Jpeg);
+    let png = image_base64(image::ImageFormat::Png);
+    assembler.add_media_file("logo.jpg", jpeg.clone(), FetchInfo::default());
+    let update = assembler
+        .add_media_file("icons/dot.png", png.clone(), FetchInfo::default())
+        .into_iter()
+        .find_map(|file| renderer.render(file))
+        .unwrap();
+
+    assert!(update.complete);
+    let written = fs::read_to_string(&update.path).unwrap();
+    assert!(written.contains(&format!("src=\"data:image/jpeg;base64,{jpeg}\"")));
+    assert!(written.contains(&format!("src=\"data:image/png;base64,{png}\"")));
+    let entries = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<PathBuf>>();
+    assert_eq!(entries, vec![update.path]);
+
+    let _ = fs::remove_dir_all(dir);
+}
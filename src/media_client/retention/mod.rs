@@ -0,0 +1,276 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use log::error;
+use sha2::{Digest, Sha256};
+
+use super::{
+    clock::{Clock, SystemClock},
+    config::RetentionLimits,
+    manifest::MANIFEST_FILE,
+};
+
+#[cfg(test)]
+mod test;
+
+type Sha256Digest = [u8; 32];
+
+/// A document written in the output directory, either a bundle directory or a single html
+struct Output {
+    path: PathBuf,
+    modified: SystemTime,
+    bytes: u64,
+}
+
+/// Keeps the output directory within its `RetentionLimits`
+/// and makes sure the same document is not stored twice.
+///
+/// Entries whose name starts with `.`, like the caches, are never touched.
+pub struct RetentionManager {
+    dir: PathBuf,
+    limits: RetentionLimits,
+    /// digest of the files of each output -> its path, `None` until the directory is read
+    digests: Option<HashMap<Sha256Digest, PathBuf>>,
+    /// outputs registered with `add`, the only ones managed if the directory is shared,
+    /// `None` if every output of the directory belongs to the client
+    owned: Option<HashSet<PathBuf>>,
+    /// when each output was given to `add`, used instead of its modification time
+    added_at: HashMap<PathBuf, SystemTime>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RetentionManager {
+    pub fn new(dir: PathBuf, limits: RetentionLimits) -> Self {
+        Self {
+            dir,
+            limits,
+            digests: None,
+            owned: None,
+            added_at: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+    /// Replaces the `SystemClock` giving the age of the outputs
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    /// The directory is shared with other clients: only the outputs given to `add`
+    /// are compared, counted and removed
+    #[must_use]
+    pub fn shared(mut self) -> Self {
+        self.owned = Some(HashSet::new());
+        self
+    }
+    /// Registers the output just written at `path`.
+    ///
+    /// # Returns
+    /// `path`, or an older identical output, in which case `path` is removed
+    pub fn add(&mut self, path: &Path) -> PathBuf {
+        let Some(digest) = output_digest(path)
+            .inspect_err(|e| {
+                error!("[mediaclient] error reading {}: {e}", path.display());
+            })
+            .ok()
+        else {
+            return path.to_path_buf();
+        };
+        let digests = self.digests(path);
+        let kept = match digests.get(&digest) {
+            Some(existing) if existing != path && existing.exists() => {
+                let existing = existing.clone();
+                remove_output(path);
+                existing
+            }
+            _ => {
+                digests.insert(digest, path.to_path_buf());
+                path.to_path_buf()
+            }
+        };
+        if let Some(owned) = &mut self.owned {
+            owned.insert(kept.clone());
+        }
+        self.added_at.insert(kept.clone(), self.clock.system_time());
+        kept
+    }
+    /// Removes the oldest outputs until the limits are respected, `written` is always kept
+    ///
+    /// # Returns
+    /// the number of outputs removed
+    pub fn enforce(&mut self, written: &Path) -> usize {
+        if self.limits == RetentionLimits::default() {
+            return 0;
+        }
+        let mut outputs = match self.list_outputs() {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                error!("[mediaclient] error reading {}: {e}", self.dir.display());
+                return 0;
+            }
+        };
+        outputs.sort_unstable_by_key(|output| Reverse(output.modified));
+        // `written` is counted first, as if it were the newest
+        outputs.sort_by_key(|output| output.path != written);
+        let now = self.clock.system_time();
+        let mut kept_bytes = 0;
+        let mut removed = Vec::new();
+        for (i, output) in outputs.into_iter().enumerate() {
+            if output.path == written {
+                kept_bytes += output.bytes;
+                continue;
+            }
+            let too_many = self.limits.max_bundles.is_some_and(|max| i >= max);
+            let too_old = self.limits.max_age.is_some_and(|max_age| {
+                now.duration_since(output.modified)
+                    .is_ok_and(|age| age > max_age)
+            });
+            let too_big = self
+                .limits
+                .max_bytes
+                .is_some_and(|max| kept_bytes + output.bytes > max);
+            if too_many || too_old || too_big {
+                remove_output(&output.path);
+                removed.push(output.path);
+            } else {
+                kept_bytes += output.bytes;
+            }
+        }
+        self.forget(&removed);
+        removed.len()
+    }
+    /// Removes every output
+    ///
+    /// # Returns
+    /// the number of outputs removed
+    pub fn purge(&mut self) -> usize {
+        let outputs = match self.list_outputs() {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                error!("[mediaclient] error reading {}: {e}", self.dir.display());
+                return 0;
+            }
+        };
+        for output in &outputs {
+            remove_output(&output.path);
+        }
+        self.digests = Some(HashMap::new());
+        if let Some(owned) = &mut self.owned {
+            owned.clear();
+        }
+        self.added_at.clear();
+        outputs.len()
+    }
+    /// Outputs of the directory managed by this client, the ones given to `add`
+    /// dated by the `Clock`
+    fn list_outputs(&self) -> io::Result<Vec<Output>> {
+        let mut outputs = list_outputs(&self.dir)?;
+        if let Some(owned) = &self.owned {
+            outputs.retain(|output| owned.contains(&output.path));
+        }
+        for output in &mut outputs {
+            if let Some(added_at) = self.added_at.get(&output.path) {
+                output.modified = *added_at;
+            }
+        }
+        Ok(outputs)
+    }
+    /// Reads the directory the first time, leaving out `written`
+    fn digests(&mut self, written: &Path) -> &mut HashMap<Sha256Digest, PathBuf> {
+        if self.digests.is_none() {
+            let digests = self
+                .list_outputs()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|output| output.path != written)
+                .filter_map(|output| Some((output_digest(&output.path).ok()?, output.path)))
+                .collect();
+            self.digests = Some(digests);
+        }
+        self.digests.get_or_insert_with(HashMap::new)
+    }
+    fn forget(&mut self, removed: &[PathBuf]) {
+        if let Some(digests) = &mut self.digests {
+            digests.retain(|_, path| !removed.contains(path));
+        }
+        if let Some(owned) = &mut self.owned {
+            owned.retain(|path| !removed.contains(path));
+        }
+        self.added_at.retain(|path, _| !removed.contains(path));
+    }
+}
+
+fn list_outputs(dir: &Path) -> io::Result<Vec<Output>> {
+    let mut outputs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        outputs.push(Output {
+            modified: entry.metadata()?.modified()?,
+            bytes: output_bytes(&path)?,
+            path,
+        });
+    }
+    Ok(outputs)
+}
+
+/// Files of an output as `(path relative to the output, path)`, sorted
+fn output_files(path: &Path) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    if !path.is_dir() {
+        return Ok(vec![(PathBuf::new(), path.to_path_buf())]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push((PathBuf::from(entry.file_name()), entry.path()));
+        }
+    }
+    files.sort_unstable();
+    Ok(files)
+}
+
+fn output_bytes(path: &Path) -> io::Result<u64> {
+    output_files(path)?
+        .into_iter()
+        .map(|(_, file)| Ok(fs::metadata(file)?.len()))
+        .sum()
+}
+
+/// sha256 of the names and contents of the files of an output, the manifest is left out
+/// because it records when the files were fetched
+fn output_digest(path: &Path) -> io::Result<Sha256Digest> {
+    let mut hasher = Sha256::new();
+    for (name, file) in output_files(path)? {
+        if name == Path::new(MANIFEST_FILE) {
+            continue;
+        }
+        let content = fs::read(file)?;
+        let name = name.to_string_lossy();
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn remove_output(path: &Path) {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    let _ = result.inspect_err(|e| {
+        error!("[mediaclient] error removing {}: {e}", path.display());
+    });
+}
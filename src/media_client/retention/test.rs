@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use super::*;
+use crate::media_client::{test_util::test_dir, ManualClock};
+
+fn write_bundle(dir: &Path, name: &str, html: &str) -> PathBuf {
+    let bundle = dir.join(name);
+    fs::create_dir_all(&bundle).unwrap();
+    fs::write(bundle.join("file.html"), html).unwrap();
+    fs::write(bundle.join("media.jpg"), [0xff, 0xd8, 0xff]).unwrap();
+    fs::write(
+        bundle.join(MANIFEST_FILE),
+        format!("{{\"bundle\":\"{name}\"}}"),
+    )
+    .unwrap();
+    bundle
+}
+
+#[test]
+fn test_add_keeps_the_first_of_identical_bundles() {
+    let dir = test_dir("dedup");
+    let mut retention = RetentionManager::new(dir.clone(), RetentionLimits::default());
+
+    let first = write_bundle(&dir, "1_file_100", "<img src=\"media.jpg\">");
+    assert_eq!(retention.add(&first), first);
+    let second = write_bundle(&dir, "1_file_101", "<img src=\"media.jpg\">");
+    assert_eq!(retention.add(&second), first);
+    assert!(!second.exists());
+
+    let different = write_bundle(&dir, "1_file_102", "<p>changed</p>");
+    assert_eq!(retention.add(&different), different);
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_add_finds_bundles_written_before_it_was_created() {
+    let dir = test_dir("dedup_existing");
+    let first = write_bundle(&dir, "1_file_100", "<p>same</p>");
+
+    let mut retention = RetentionManager::new(dir.clone(), RetentionLimits::default());
+    let second = write_bundle(&dir, "1_file_101", "<p>same</p>");
+    assert_eq!(retention.add(&second), first);
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_enforce_removes_the_oldest_bundles() {
+    let dir = test_dir("max_bundles");
+    let limits = RetentionLimits {
+        max_bundles: Some(2),
+        ..RetentionLimits::default()
+    };
+    let clock = Arc::new(ManualClock::default());
+    let mut retention = RetentionManager::new(dir.clone(), limits).with_clock(clock.clone());
+    fs::create_dir_all(dir.join(".text_cache")).unwrap();
+    let mut bundles = Vec::new();
+    for i in 0..3 {
+        let bundle = write_bundle(&dir, &format!("1_file_{i}"), &format!("<p>{i}</p>"));
+        bundles.push(retention.add(&bundle));
+        clock.advance(Duration::from_secs(1));
+    }
+
+    assert_eq!(retention.enforce(&bundles[2]), 1);
+    assert!(!bundles[0].exists());
+    assert!(bundles[1].exists() && bundles[2].exists());
+    assert!(dir.join(".text_cache").exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_enforce_keeps_the_newest_bundles_within_max_bytes() {
+    let dir = test_dir("max_bytes");
+    let newest_bytes = output_bytes(&write_bundle(&dir, "placeholder", "<p>2</p>")).unwrap();
+    fs::remove_dir_all(dir.join("placeholder")).unwrap();
+    let limits = RetentionLimits {
+        max_bytes: Some(newest_bytes),
+        ..RetentionLimits::default()
+    };
+    let clock = Arc::new(ManualClock::default());
+    let mut retention = RetentionManager::new(dir.clone(), limits).with_clock(clock.clone());
+    let old = retention.add(&write_bundle(&dir, "1_file_1", "<p>1</p>"));
+    clock.advance(Duration::from_secs(1));
+    let new = retention.add(&write_bundle(&dir, "1_file_2", "<p>2</p>"));
+
+    assert_eq!(retention.enforce(&new), 1);
+    assert!(!old.exists());
+    assert!(new.exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_purge_removes_every_output_but_the_caches() {
+    let dir = test_dir("purge");
+    let mut retention = RetentionManager::new(dir.clone(), RetentionLimits::default());
+    fs::create_dir_all(dir.join(".media_cache")).unwrap();
+    write_bundle(&dir, "1_file_1", "<p>1</p>");
+    fs::write(dir.join("1_file_2.html"), "<p>2</p>").unwrap();
+
+    assert_eq!(retention.purge(), 2);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    assert!(dir.join(".media_cache").exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_enforce_never_removes_the_written_bundle() {
+    let dir = test_dir("written");
+    let limits = RetentionLimits {
+        max_bytes: Some(1),
+        ..RetentionLimits::default()
+    };
+    let clock = Arc::new(ManualClock::default());
+    let mut retention = RetentionManager::new(dir.clone(), limits).with_clock(clock.clone());
+    let new = retention.add(&write_bundle(&dir, "1_file_2", "<p>2</p>"));
+    clock.advance(Duration::from_secs(1));
+    // added after `new`, like a document completed later than it was started
+    let old = retention.add(&write_bundle(&dir, "1_file_1", "<p>1</p>"));
+
+    assert_eq!(retention.enforce(&new), 1);
+    assert!(new.exists());
+    assert!(!old.exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_shared_directory_keeps_the_outputs_of_other_clients() {
+    let dir = test_dir("shared");
+    let limits = RetentionLimits {
+        max_bundles: Some(1),
+        ..RetentionLimits::default()
+    };
+    let clock = Arc::new(ManualClock::default());
+    let mut retention = RetentionManager::new(dir.clone(), limits)
+        .with_clock(clock.clone())
+        .shared();
+    let other = write_bundle(&dir, "1_file_1", "<p>same</p>");
+    let first = write_bundle(&dir, "1_file_2", "<p>same</p>");
+    assert_eq!(retention.add(&first), first);
+    clock.advance(Duration::from_secs(1));
+    let second = write_bundle(&dir, "1_file_3", "<p>3</p>");
+    assert_eq!(retention.add(&second), second);
+
+    assert_eq!(retention.enforce(&second), 1);
+    assert!(!first.exists());
+    assert!(other.exists() && second.exists());
+
+    assert_eq!(retention.purge(), 1);
+    assert!(!second.exists());
+    assert!(other.exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_enforce_measures_the_age_with_the_clock() {
+    let dir = test_dir("max_age");
+    let limits = RetentionLimits {
+        max_age: Some(Duration::from_secs(10)),
+        ..RetentionLimits::default()
+    };
+    let clock = Arc::new(ManualClock::new(SystemTime::now()));
+    let mut retention = RetentionManager::new(dir.clone(), limits).with_clock(clock.clone());
+    let old = write_bundle(&dir, "1_file_1", "<p>1</p>");
+    retention.add(&old);
+
+    clock.advance(Duration::from_secs(10));
+    let new = write_bundle(&dir, "1_file_2", "<p>2</p>");
+    retention.add(&new);
+    assert_eq!(retention.enforce(&new), 0);
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(retention.enforce(&new), 1);
+    assert!(!old.exists());
+    assert!(new.exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
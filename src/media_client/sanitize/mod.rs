@@ -0,0 +1,82 @@
+use std::{fmt::Display, path::Path};
+
+#[cfg(test)]
+mod test;
+
+/// longest `file_id` or `media_id` accepted from a server
+pub const MAX_NAME_LEN: usize = 128;
+
+/// Why a name sent by a server cannot be used in a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameRejection {
+    Empty,
+    TooLong,
+    Absolute,
+    ParentDir,
+    ControlChar,
+}
+
+impl Display for NameRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty name"),
+            Self::TooLong => write!(f, "longer than {MAX_NAME_LEN} bytes"),
+            Self::Absolute => write!(f, "absolute path"),
+            Self::ParentDir => write!(f, "contains `..`"),
+            Self::ControlChar => write!(f, "contains control characters"),
+        }
+    }
+}
+
+/// Checks a `file_id` or `media_id` before it gets anywhere near the filesystem
+///
+/// # Errors
+/// The first rule the name breaks
+pub fn validate_name(name: &str) -> Result<(), NameRejection> {
+    if name.is_empty() {
+        return Err(NameRejection::Empty);
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(NameRejection::TooLong);
+    }
+    if name.chars().any(char::is_control) {
+        return Err(NameRejection::ControlChar);
+    }
+    let has_drive = name.as_bytes().get(1) == Some(&b':');
+    if name.starts_with(['/', '\\']) || has_drive || Path::new(name).is_absolute() {
+        return Err(NameRejection::Absolute);
+    }
+    if name.split(['/', '\\']).any(|component| component == "..") {
+        return Err(NameRejection::ParentDir);
+    }
+    Ok(())
+}
+
+/// Turns a name into a single path component:
+/// validated, every char outside `[A-Za-z0-9._-]` replaced by `_`, without leading dots
+///
+/// # Errors
+/// The first rule of `validate_name` the name breaks
+pub fn sanitize_component(name: &str) -> Result<String, NameRejection> {
+    validate_name(name)?;
+    let component = safe_chars(name);
+    let component = component.trim_start_matches('.');
+    Ok(if component.is_empty() {
+        "_".to_owned()
+    } else {
+        component.to_owned()
+    })
+}
+
+/// `name` with every char outside `[A-Za-z0-9._-]` replaced by `_`
+pub fn safe_chars(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
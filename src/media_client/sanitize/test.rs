@@ -0,0 +1,84 @@
+use crate::media_client::html_rewrite::local_media_names;
+
+use super::*;
+
+#[test]
+fn test_validate_name_rejects_hostile_names() {
+    let hostile = [
+        ("", NameRejection::Empty),
+        ("../../.bashrc", NameRejection::ParentDir),
+        ("..", NameRejection::ParentDir),
+        ("images/../../etc/passwd", NameRejection::ParentDir),
+        ("..\\..\\boot.ini", NameRejection::ParentDir),
+        ("/etc/passwd", NameRejection::Absolute),
+        ("\\\\server\\share\\x.jpg", NameRejection::Absolute),
+        ("C:\\Windows\\win.ini", NameRejection::Absolute),
+        ("c:relative.jpg", NameRejection::Absolute),
+        ("media\0.jpg", NameRejection::ControlChar),
+        ("media\n.jpg", NameRejection::ControlChar),
+    ];
+    for (name, reason) in hostile {
+        assert_eq!(validate_name(name), Err(reason), "{name:?}");
+    }
+    let too_long = "a".repeat(MAX_NAME_LEN + 1);
+    assert_eq!(validate_name(&too_long), Err(NameRejection::TooLong));
+}
+
+#[test]
+fn test_validate_name_accepts_plain_names() {
+    for name in [
+        "file1.html",
+        "media2.jpg",
+        "images/a.jpg",
+        "..hidden.jpg",
+        "a..b.png",
+        "spaces in name.jpg",
+    ] {
+        assert_eq!(validate_name(name), Ok(()), "{name:?}");
+    }
+    assert_eq!(validate_name(&"a".repeat(MAX_NAME_LEN)), Ok(()));
+}
+
+#[test]
+fn test_sanitize_component() {
+    assert_eq!(
+        sanitize_component("file1.html"),
+        Ok("file1.html".to_owned())
+    );
+    assert_eq!(
+        sanitize_component("images/a b.jpg"),
+        Ok("images_a_b.jpg".to_owned())
+    );
+    assert_eq!(sanitize_component(".bashrc"), Ok("bashrc".to_owned()));
+    assert_eq!(sanitize_component("..."), Ok("_".to_owned()));
+    assert_eq!(
+        sanitize_component("../x.html"),
+        Err(NameRejection::ParentDir)
+    );
+}
+
+#[test]
+fn test_local_media_names_stay_in_bundle() {
+    let media_ids = [
+        "images/a.jpg".to_owned(),
+        "a.jpg".to_owned(),
+        "other\\a.jpg".to_owned(),
+        ".hidden".to_owned(),
+        "noext".to_owned(),
+        "ünïcode.png".to_owned(),
+    ];
+    let local_names = local_media_names(&media_ids);
+    assert_eq!(local_names.len(), media_ids.len());
+    for local_name in local_names.values() {
+        assert!(!local_name.contains(['/', '\\']), "{local_name}");
+        assert!(!local_name.starts_with('.'), "{local_name}");
+        assert_eq!(safe_chars(local_name), *local_name);
+    }
+    let mut unique = local_names.values().collect::<Vec<_>>();
+    unique.sort_unstable();
+    unique.dedup();
+    assert_eq!(unique.len(), media_ids.len());
+    assert_eq!(local_names["a.jpg"], "a.jpg");
+    assert_eq!(local_names["noext"], "noext.jpg");
+    assert_eq!(local_names["ünïcode.png"], "_n_code.png");
+}
@@ -2,12 +2,15 @@ use colored::Colorize;
 use crossbeam_channel::Sender;
 use log::{error, info};
 use messages::client_commands::MediaClientEvent;
+use std::time::Instant;
+
 use wg_2024::{network::NodeId, packet::Packet};
 
-use super::MediaClient;
+use super::{MediaClient, MediaClientExtEvent, TraceRecord};
 
 impl MediaClient {
     pub fn send_controller(&self, msg: MediaClientEvent) {
+        self.record(|| TraceRecord::Event(format!("{msg:?}")));
         self.controller_send
             .send(msg)
             .inspect_err(|e| {
@@ -20,11 +23,50 @@ impl MediaClient {
             })
             .ok();
     }
+    /// Does nothing if the ext controller channels are not connected
+    pub fn send_ext_controller(&self, msg: MediaClientExtEvent) {
+        let Some(ext_controller_send) = &self.ext_controller_send else {
+            return;
+        };
+        ext_controller_send
+            .send(msg)
+            .inspect_err(|e| {
+                error!(
+                    "{} [MediaClient {}] error in sending to ext controller. Message: [{:?}]",
+                    "✗".red(),
+                    self.id,
+                    e.0
+                );
+            })
+            .ok();
+    }
+    /// Sends a fragment of a message of the client, kept in the `PacketCache` until it is acked
+    pub(super) fn send_fragment(&mut self, fragment: Packet) {
+        self.packet_cache.insert_packet(&fragment);
+        self.unacked_fragments
+            .entry(fragment.session_id)
+            .or_default()
+            .insert(fragment.get_fragment_index());
+        self.send_packet(fragment, None);
+    }
     /// Used to send packet
     ///
     /// # Arguments
     /// -  `sender` : to be included only if  `msg`  is of type  `FloodRequest` otherwise it will be ignored
     pub fn send_packet(&self, msg: Packet, sender: Option<&Sender<Packet>>) {
+        for msg in self.faults.outbound(msg, self.clock.now()) {
+            self.transmit(msg, sender);
+        }
+    }
+    /// Sends the `Ack`s held by the `FaultInjector` until `due`, or until now if it is later
+    pub(super) fn send_delayed_acks(&self, due: Instant) {
+        for ack in self.faults.take_due(due.max(self.clock.now())) {
+            self.transmit(ack, None);
+        }
+    }
+    fn transmit(&self, msg: Packet, sender: Option<&Sender<Packet>>) {
+        self.record(|| TraceRecord::PacketOut(msg.clone()));
+        self.metrics.borrow_mut().packet_sent(&msg);
         match msg.pack_type {
             wg_2024::packet::PacketType::Ack(_)
             | wg_2024::packet::PacketType::Nack(_)
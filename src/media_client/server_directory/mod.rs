@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    mem::discriminant,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use messages::high_level_messages::ServerType;
+use wg_2024::network::NodeId;
+
+use super::clock::{Clock, SystemClock};
+
+#[cfg(test)]
+mod test;
+
+/// how long a `ServerType` is trusted before asking again
+pub const DEFAULT_SERVER_TYPE_TTL: Duration = Duration::from_secs(60);
+/// how long to wait for a `ServerType` before asking again
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct DirectoryEntry {
+    server_type: Option<ServerType>,
+    updated_at: Option<Instant>,
+    queried_at: Option<Instant>,
+}
+
+impl DirectoryEntry {
+    fn is_waiting(&self, now: Instant) -> bool {
+        self.queried_at
+            .is_some_and(|queried_at| now.duration_since(queried_at) < QUERY_TIMEOUT)
+    }
+    fn is_expired(&self, now: Instant, ttl: Duration) -> bool {
+        self.updated_at
+            .is_none_or(|updated_at| now.duration_since(updated_at) >= ttl)
+    }
+}
+
+/// Keeps the `ServerType` of every server found through flooding,
+/// so that each server is asked only once per `ttl`.
+pub struct ServerDirectory {
+    entries: HashMap<NodeId, DirectoryEntry>,
+    ttl: Duration,
+    /// something changed since the last `take_changes`
+    changed: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ServerDirectory {
+    fn default() -> Self {
+        Self::new(DEFAULT_SERVER_TYPE_TTL, Arc::new(SystemClock))
+    }
+}
+
+impl ServerDirectory {
+    pub fn new(ttl: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            entries: HashMap::new(),
+            ttl,
+            changed: false,
+            clock,
+        }
+    }
+    /// Synchronizes the directory with the servers known by the router.
+    ///
+    /// # Returns
+    /// the servers that need a `GetServerType`: unknown, expired or not answering.
+    /// They are considered queried from now on.
+    pub fn servers_to_query(&mut self, servers: impl IntoIterator<Item = NodeId>) -> Vec<NodeId> {
+        let now = self.clock.now();
+        let servers = servers.into_iter().collect::<Vec<NodeId>>();
+        let before = self.entries.len();
+        self.entries.retain(|id, _| servers.contains(id));
+        self.changed |= before != self.entries.len();
+
+        let mut to_query = Vec::new();
+        for server in servers {
+            let entry = self.entries.entry(server).or_default();
+            if entry.is_expired(now, self.ttl) && !entry.is_waiting(now) {
+                entry.queried_at = Some(now);
+                to_query.push(server);
+            }
+        }
+        to_query.sort_unstable();
+        to_query
+    }
+    pub fn set_server_type(&mut self, id: NodeId, server_type: ServerType) {
+        let entry = self.entries.entry(id).or_default();
+        let same_type = entry
+            .server_type
+            .as_ref()
+            .is_some_and(|old| discriminant(old) == discriminant(&server_type));
+        self.changed |= !same_type;
+        entry.server_type = Some(server_type);
+        entry.updated_at = Some(self.clock.now());
+        entry.queried_at = None;
+    }
+    /// # Returns
+    /// the whole directory if it changed since the last call and no server is still being queried
+    pub fn take_changes(&mut self) -> Option<Vec<(NodeId, ServerType)>> {
+        let now = self.clock.now();
+        if !self.changed || self.entries.values().any(|entry| entry.is_waiting(now)) {
+            return None;
+        }
+        self.changed = false;
+        Some(self.entries())
+    }
+    /// Classified servers, sorted by id
+    pub fn entries(&self) -> Vec<(NodeId, ServerType)> {
+        let mut entries = self
+            .entries
+            .iter()
+            .filter_map(|(&id, entry)| Some((id, entry.server_type.clone()?)))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(id, _)| *id);
+        entries
+    }
+    pub fn server_type(&self, id: NodeId) -> Option<&ServerType> {
+        self.entries.get(&id)?.server_type.as_ref()
+    }
+    /// Media servers, sorted by id
+    pub fn media_servers(&self) -> Vec<NodeId> {
+        let mut media_servers = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| matches!(entry.server_type, Some(ServerType::Media)))
+            .map(|(&id, _)| id)
+            .collect::<Vec<NodeId>>();
+        media_servers.sort_unstable();
+        media_servers
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use assembler::HighLevelMessageFactory;
+use crossbeam_channel::unbounded;
+use messages::high_level_messages::{MessageContent::FromServer, ServerMessage};
+use wg_2024::{
+    network::SourceRoutingHeader,
+    packet::{FloodResponse, NodeType, Packet, PacketType},
+};
+
+use super::*;
+use crate::media_client::{test_util, ManualClock, MediaClient, MediaClientExtEvent};
+
+const CLIENT: NodeId = 1;
+const DRONE: NodeId = 11;
+
+#[test]
+fn test_server_directory_expires_with_the_clock() {
+    let clock = Arc::new(ManualClock::default());
+    let mut directory = ServerDirectory::new(DEFAULT_SERVER_TYPE_TTL, clock.clone());
+
+    assert_eq!(directory.servers_to_query([5]), vec![5]);
+    assert!(directory.servers_to_query([5]).is_empty());
+    directory.set_server_type(5, ServerType::Media);
+    assert!(directory.servers_to_query([5]).is_empty());
+
+    clock.advance(DEFAULT_SERVER_TYPE_TTL);
+    assert_eq!(directory.servers_to_query([5]), vec![5]);
+}
+
+fn waiting_directory() -> (Arc<ManualClock>, ServerDirectory) {
+    let clock = Arc::new(ManualClock::default());
+    let mut directory = ServerDirectory::new(DEFAULT_SERVER_TYPE_TTL, clock.clone());
+    assert_eq!(directory.servers_to_query([22, 21]), vec![21, 22]);
+    (clock, directory)
+}
+
+#[test]
+fn test_changes_wait_for_every_query() {
+    let (_clock, mut directory) = waiting_directory();
+    assert!(directory.take_changes().is_none());
+
+    directory.set_server_type(21, ServerType::Text);
+    assert!(directory.take_changes().is_none());
+    directory.set_server_type(22, ServerType::Media);
+    assert!(matches!(
+        directory.take_changes().as_deref(),
+        Some([(21, ServerType::Text), (22, ServerType::Media)])
+    ));
+    assert!(directory.take_changes().is_none());
+}
+
+#[test]
+fn test_changes_after_a_query_times_out() {
+    let (clock, mut directory) = waiting_directory();
+    directory.set_server_type(21, ServerType::Text);
+
+    clock.advance(QUERY_TIMEOUT);
+    assert!(matches!(
+        directory.take_changes().as_deref(),
+        Some([(21, ServerType::Text)])
+    ));
+}
+
+#[test]
+fn test_only_real_changes_are_reported() {
+    let (_clock, mut directory) = waiting_directory();
+    directory.set_server_type(21, ServerType::Text);
+    directory.set_server_type(22, ServerType::Media);
+    assert!(directory.take_changes().is_some());
+
+    directory.set_server_type(22, ServerType::Media);
+    assert!(directory.take_changes().is_none());
+
+    directory.set_server_type(22, ServerType::Text);
+    assert!(matches!(
+        directory.take_changes().as_deref(),
+        Some([(21, ServerType::Text), (22, ServerType::Text)])
+    ));
+
+    assert!(directory.servers_to_query([21]).is_empty());
+    assert!(matches!(
+        directory.take_changes().as_deref(),
+        Some([(21, ServerType::Text)])
+    ));
+}
+
+/// Answers `GetServerType` as `server`, through `DRONE`
+fn server_type_answer(server: NodeId, server_type: ServerType) -> Vec<Packet> {
+    let mut factory = HighLevelMessageFactory::new(server, NodeType::Server);
+    factory
+        .get_message_from_message_content(
+            FromServer(ServerMessage::ServerType(server_type)),
+            &SourceRoutingHeader::with_first_hop(vec![server, DRONE, CLIENT]),
+            CLIENT,
+        )
+        .into_iter()
+        .map(|mut fragment| {
+            fragment.routing_header.hop_index = 2;
+            fragment
+        })
+        .collect()
+}
+
+#[test]
+fn test_client_sends_one_directory_event() {
+    let (drone_send, _drone_recv) = unbounded();
+    let (ext_send, ext_recv) = unbounded();
+    let mut client = MediaClient::new(
+        CLIENT,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(DRONE, drone_send)]),
+        test_util::test_config("server_directory"),
+    )
+    .with_ext_controller(ext_send, unbounded().1);
+    for server in [21, 22] {
+        client.handle_packet(Packet {
+            routing_header: SourceRoutingHeader::new(vec![server, DRONE, CLIENT], 2),
+            session_id: 0,
+            pack_type: PacketType::FloodResponse(FloodResponse {
+                flood_id: 1,
+                path_trace: vec![
+                    (CLIENT, NodeType::Client),
+                    (DRONE, NodeType::Drone),
+                    (server, NodeType::Server),
+                ],
+            }),
+        });
+    }
+
+    for fragment in server_type_answer(21, ServerType::Text)
+        .into_iter()
+        .chain(server_type_answer(22, ServerType::Media))
+    {
+        client.handle_packet(fragment);
+    }
+    let directories = ext_recv
+        .try_iter()
+        .filter_map(|event| match event {
+            MediaClientExtEvent::ServerDirectoryChanged(directory) => Some(directory),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(directories.len(), 1);
+    assert!(matches!(
+        directories[0][..],
+        [(21, ServerType::Text), (22, ServerType::Media)]
+    ));
+}
@@ -0,0 +1,22 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use super::{ManualClock, MediaClientConfig, ViewerLaunch};
+
+/// Empty directory in the system temp directory, unique to `name`
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("media_client_{name}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Config writing in `test_dir(name)`, without viewer, on a `ManualClock` and with a fixed seed
+pub(crate) fn test_config(name: &str) -> MediaClientConfig {
+    MediaClientConfig {
+        output_root: test_dir(name),
+        viewer: ViewerLaunch::None,
+        clock: Arc::new(ManualClock::default()),
+        seed: Some(0),
+        ..MediaClientConfig::default()
+    }
+}
@@ -0,0 +1,135 @@
+use std::{fs, path::PathBuf, sync::Arc, time::Duration};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+
+use super::{
+    clock::{Clock, SystemClock},
+    media_cache::hex_encode,
+};
+
+#[cfg(test)]
+mod test;
+
+/// directory inside the output directory of the client, see `MediaClientConfig::output_dir`
+pub const TEXT_CACHE_DIR: &str = ".text_cache";
+
+/// When an `AskForFile` is answered from the cache instead of the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TextCachePolicy {
+    /// always ask the server, the cache is only used by `OpenCachedFile`
+    #[default]
+    AlwaysRefetch,
+    /// use the cached copy if it was fetched less than the given time ago
+    MaxAge(Duration),
+    /// use the cached copy whenever there is one
+    PreferCache,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedTextFile {
+    pub source_id: NodeId,
+    pub file_id: String,
+    pub content: String,
+    pub size: usize,
+    /// seconds since `UNIX_EPOCH`
+    pub fetched_at: u64,
+}
+
+impl CachedTextFile {
+    fn age(&self, clock: &dyn Clock) -> Duration {
+        Duration::from_secs(clock.unix_secs().saturating_sub(self.fetched_at))
+    }
+}
+
+/// Text files received from the servers, stored on disk as
+/// `{dir}/{source_id}/{hex(file_id)}.json`
+pub struct TextCache {
+    dir: Option<PathBuf>,
+    policy: TextCachePolicy,
+    clock: Arc<dyn Clock>,
+}
+
+impl TextCache {
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir: Some(dir),
+            policy: TextCachePolicy::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+    /// Cache that stores nothing
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            dir: None,
+            policy: TextCachePolicy::AlwaysRefetch,
+            clock: Arc::new(SystemClock),
+        }
+    }
+    /// Replaces the `SystemClock` used for `fetched_at` and the `MaxAge` policy
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    #[must_use]
+    pub fn with_policy(mut self, policy: TextCachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    pub fn set_policy(&mut self, policy: TextCachePolicy) {
+        self.policy = policy;
+    }
+    pub fn get(&self, source_id: NodeId, file_id: &str) -> Option<CachedTextFile> {
+        let json = fs::read_to_string(self.path(source_id, file_id)?).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+    pub fn contains(&self, source_id: NodeId, file_id: &str) -> bool {
+        self.path(source_id, file_id)
+            .is_some_and(|path| path.exists())
+    }
+    /// # Returns
+    /// the cached copy if the policy allows to use it instead of asking the server
+    pub fn fresh(&self, source_id: NodeId, file_id: &str) -> Option<CachedTextFile> {
+        let max_age = match self.policy {
+            TextCachePolicy::AlwaysRefetch => return None,
+            TextCachePolicy::MaxAge(max_age) => max_age,
+            TextCachePolicy::PreferCache => Duration::MAX,
+        };
+        self.get(source_id, file_id)
+            .filter(|cached| cached.age(self.clock.as_ref()) < max_age)
+    }
+    pub fn insert(&self, source_id: NodeId, file_id: &str, content: &str, size: usize) {
+        let Some(path) = self.path(source_id, file_id) else {
+            return;
+        };
+        let cached = CachedTextFile {
+            source_id,
+            file_id: file_id.to_owned(),
+            content: content.to_owned(),
+            size,
+            fetched_at: self.clock.unix_secs(),
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| {
+                let json = serde_json::to_string(&cached)?;
+                fs::write(&path, json)
+            });
+        let _ = result.inspect_err(|e| {
+            error!("[mediaclient] error caching file {source_id}_{file_id}: {e}");
+        });
+    }
+    fn path(&self, source_id: NodeId, file_id: &str) -> Option<PathBuf> {
+        Some(
+            self.dir
+                .as_ref()?
+                .join(source_id.to_string())
+                .join(format!("{}.json", hex_encode(file_id))),
+        )
+    }
+}
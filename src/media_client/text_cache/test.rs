@@ -0,0 +1,37 @@
+use std::time::UNIX_EPOCH;
+
+use super::*;
+use crate::media_client::{test_util::test_dir, ManualClock, MediaClientConfig};
+
+#[test]
+fn test_text_cache_max_age_follows_the_clock() {
+    let dir = test_dir("text_cache");
+    let clock = Arc::new(ManualClock::new(UNIX_EPOCH + Duration::from_secs(86_400)));
+    let cache = TextCache::new(dir.clone())
+        .with_policy(TextCachePolicy::MaxAge(Duration::from_secs(60)))
+        .with_clock(clock.clone());
+
+    cache.insert(1, "index.html", "<p>cached</p>", 13);
+    assert_eq!(cache.get(1, "index.html").unwrap().fetched_at, 86_400);
+    assert!(cache.fresh(1, "index.html").is_some());
+    clock.advance(Duration::from_secs(60));
+    assert!(cache.fresh(1, "index.html").is_none());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_default_policy_always_asks_the_server() {
+    let dir = test_dir("text_cache_default");
+    let cache = TextCache::new(dir.clone());
+
+    cache.insert(1, "index.html", "<p>cached</p>", 13);
+    assert!(cache.contains(1, "index.html"));
+    assert!(cache.fresh(1, "index.html").is_none());
+    assert_eq!(
+        MediaClientConfig::default().text_cache_policy,
+        TextCachePolicy::AlwaysRefetch
+    );
+
+    let _ = fs::remove_dir_all(dir);
+}
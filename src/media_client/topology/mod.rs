@@ -0,0 +1,182 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt::Write,
+};
+
+use serde::Serialize;
+use wg_2024::{network::NodeId, packet::NodeType};
+
+#[cfg(test)]
+mod test;
+
+/// upper bound on the partial paths explored by `Topology::routes_to`
+const MAX_EXPANSIONS: usize = 10_000;
+
+/// Copy of the graph learned through flooding, kept next to the `Router`
+/// so that it can be inspected from outside the client.
+#[derive(Debug)]
+pub struct Topology {
+    client_id: NodeId,
+    nodes: BTreeMap<NodeId, NodeType>,
+    /// undirected, stored as `(min, max)`
+    edges: BTreeSet<(NodeId, NodeId)>,
+    crashed: BTreeSet<NodeId>,
+    dropped: HashMap<NodeId, u64>,
+}
+
+impl Topology {
+    pub fn new(client_id: NodeId) -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(client_id, NodeType::Client);
+        Self {
+            client_id,
+            nodes,
+            edges: BTreeSet::new(),
+            crashed: BTreeSet::new(),
+            dropped: HashMap::new(),
+        }
+    }
+    /// Adds every node and every link found in the `path_trace` of a `FloodResponse`
+    pub fn add_path_trace(&mut self, path_trace: &[(NodeId, NodeType)]) {
+        for &(id, node_type) in path_trace {
+            self.nodes.insert(id, node_type);
+            self.crashed.remove(&id);
+        }
+        for pair in path_trace.windows(2) {
+            self.add_edge(pair[0].0, pair[1].0);
+        }
+    }
+    pub fn add_neighbour(&mut self, id: NodeId) {
+        self.nodes.entry(id).or_insert(NodeType::Drone);
+        self.add_edge(self.client_id, id);
+    }
+    pub fn remove_neighbour(&mut self, id: NodeId) {
+        self.edges.remove(&edge(self.client_id, id));
+    }
+    pub fn drone_crashed(&mut self, id: NodeId) {
+        self.crashed.insert(id);
+        self.edges.retain(|&(a, b)| a != id && b != id);
+    }
+    pub fn record_drop(&mut self, id: NodeId) {
+        *self.dropped.entry(id).or_default() += 1;
+    }
+    /// Simple paths from the client to `destination`, shortest first.
+    /// Only drones that are not crashed are used as intermediate hops.
+    pub fn routes_to(&self, destination: NodeId, limit: usize) -> Vec<Vec<NodeId>> {
+        let mut routes = Vec::new();
+        let mut queue = VecDeque::from([vec![self.client_id]]);
+        let mut expanded = 0;
+        while let Some(path) = queue.pop_front() {
+            if routes.len() >= limit || expanded >= MAX_EXPANSIONS {
+                break;
+            }
+            expanded += 1;
+            let Some(&last) = path.last() else {
+                continue;
+            };
+            for next in self.neighbours(last) {
+                if path.contains(&next) {
+                    continue;
+                }
+                let mut next_path = path.clone();
+                next_path.push(next);
+                if next == destination {
+                    routes.push(next_path);
+                } else if self.is_usable_hop(next) {
+                    queue.push_back(next_path);
+                }
+            }
+        }
+        routes.truncate(limit);
+        routes
+    }
+    pub fn snapshot(&self) -> TopologySnapshot {
+        TopologySnapshot {
+            client_id: self.client_id,
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(&id, &node_type)| NodeInfo {
+                    id,
+                    node_type,
+                    dropped: self.dropped.get(&id).copied().unwrap_or_default(),
+                })
+                .collect(),
+            edges: self.edges.iter().copied().collect(),
+            crashed: self.crashed.iter().copied().collect(),
+        }
+    }
+    fn neighbours(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.edges
+            .iter()
+            .filter_map(move |&(a, b)| match (a == id, b == id) {
+                (true, _) => Some(b),
+                (_, true) => Some(a),
+                _ => None,
+            })
+    }
+    fn is_usable_hop(&self, id: NodeId) -> bool {
+        matches!(self.nodes.get(&id), Some(NodeType::Drone)) && !self.crashed.contains(&id)
+    }
+    fn add_edge(&mut self, a: NodeId, b: NodeId) {
+        if a != b {
+            self.edges.insert(edge(a, b));
+        }
+    }
+}
+
+fn edge(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+    (a.min(b), a.max(b))
+}
+
+/// What the client knows about the network at a given moment
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologySnapshot {
+    pub client_id: NodeId,
+    pub nodes: Vec<NodeInfo>,
+    pub edges: Vec<(NodeId, NodeId)>,
+    pub crashed: Vec<NodeId>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeInfo {
+    pub id: NodeId,
+    pub node_type: NodeType,
+    /// number of `Nack::Dropped` received from this node
+    pub dropped: u64,
+}
+
+impl TopologySnapshot {
+    /// Renders the snapshot as a Graphviz `graph`.
+    /// Crashed drones are dashed and red, the client is drawn as a box.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!("graph media_client_{} {{\n", self.client_id);
+        for node in &self.nodes {
+            let mut label = format!("{}\\n{:?}", node.id, node.node_type);
+            if node.dropped > 0 {
+                let _ = write!(label, "\\ndropped: {}", node.dropped);
+            }
+            let shape = match node.node_type {
+                NodeType::Client => "box",
+                NodeType::Drone => "ellipse",
+                NodeType::Server => "doubleoctagon",
+            };
+            let _ = write!(dot, "    {} [label=\"{label}\", shape={shape}", node.id);
+            if self.crashed.contains(&node.id) {
+                dot.push_str(", style=dashed, color=red");
+            }
+            dot.push_str("];\n");
+        }
+        for (a, b) in &self.edges {
+            let _ = writeln!(dot, "    {a} -- {b};");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+    /// # Errors
+    /// Returns an error if the snapshot cannot be serialized
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
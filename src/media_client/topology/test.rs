@@ -0,0 +1,109 @@
+use super::*;
+
+/// `1 - 11 - {12, 13} - 21`, with 13 crashed and 2 drops on 12
+fn small_topology() -> Topology {
+    let mut topology = Topology::new(1);
+    topology.add_path_trace(&[
+        (1, NodeType::Client),
+        (11, NodeType::Drone),
+        (12, NodeType::Drone),
+        (21, NodeType::Server),
+    ]);
+    topology.add_path_trace(&[
+        (1, NodeType::Client),
+        (11, NodeType::Drone),
+        (13, NodeType::Drone),
+        (21, NodeType::Server),
+    ]);
+    topology.record_drop(12);
+    topology.record_drop(12);
+    topology.drone_crashed(13);
+    topology
+}
+
+#[test]
+fn test_to_dot() {
+    let expected = r#"graph media_client_1 {
+    1 [label="1\nClient", shape=box];
+    11 [label="11\nDrone", shape=ellipse];
+    12 [label="12\nDrone\ndropped: 2", shape=ellipse];
+    13 [label="13\nDrone", shape=ellipse, style=dashed, color=red];
+    21 [label="21\nServer", shape=doubleoctagon];
+    1 -- 11;
+    11 -- 12;
+    12 -- 21;
+}
+"#;
+    assert_eq!(small_topology().snapshot().to_dot(), expected);
+}
+
+#[test]
+fn test_to_json() {
+    let json = small_topology().snapshot().to_json().unwrap();
+    let expected = serde_json::json!({
+        "client_id": 1,
+        "nodes": [
+            { "id": 1, "node_type": "Client", "dropped": 0 },
+            { "id": 11, "node_type": "Drone", "dropped": 0 },
+            { "id": 12, "node_type": "Drone", "dropped": 2 },
+            { "id": 13, "node_type": "Drone", "dropped": 0 },
+            { "id": 21, "node_type": "Server", "dropped": 0 },
+        ],
+        "edges": [[1, 11], [11, 12], [12, 21]],
+        "crashed": [13],
+    });
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_routes_to_avoid_crashed_drones() {
+    let mut topology = small_topology();
+    assert_eq!(topology.routes_to(21, 5), vec![vec![1, 11, 12, 21]]);
+
+    topology.add_path_trace(&[
+        (1, NodeType::Client),
+        (14, NodeType::Drone),
+        (15, NodeType::Drone),
+        (12, NodeType::Drone),
+        (21, NodeType::Server),
+    ]);
+    assert_eq!(
+        topology.routes_to(21, 5),
+        vec![vec![1, 11, 12, 21], vec![1, 14, 15, 12, 21]]
+    );
+    assert_eq!(topology.routes_to(21, 1), vec![vec![1, 11, 12, 21]]);
+    assert!(topology
+        .routes_to(13, 5)
+        .iter()
+        .all(|route| route.len() == 3));
+}
+
+#[test]
+fn test_routes_to_stops_after_max_expansions() {
+    // every pair of the 16 drones is linked and only the last one reaches the server
+    let mut topology = Topology::new(1);
+    let drones = (10..26).collect::<Vec<NodeId>>();
+    for &a in &drones {
+        topology.add_neighbour(a);
+        for &b in &drones {
+            topology.add_path_trace(&[(a, NodeType::Drone), (b, NodeType::Drone)]);
+        }
+    }
+    topology.add_path_trace(&[(25, NodeType::Drone), (30, NodeType::Server)]);
+
+    let routes = topology.routes_to(30, usize::MAX);
+    assert!(!routes.is_empty());
+    // each expansion adds at most one route per neighbour
+    assert!(routes.len() <= MAX_EXPANSIONS * drones.len());
+    assert_eq!(routes[0], vec![1, 25, 30]);
+    assert!(routes.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+    assert!(routes.iter().all(|route| {
+        let mut hops = route.clone();
+        hops.sort_unstable();
+        hops.dedup();
+        hops.len() == route.len() && route.last() == Some(&30)
+    }));
+}
@@ -0,0 +1,219 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufRead, LineWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crossbeam_channel::Sender;
+use log::error;
+use messages::client_commands::MediaClientCommand;
+use serde::{Deserialize, Serialize};
+use wg_2024::{network::NodeId, packet::Packet};
+
+use super::{
+    clock::{Clock, SystemClock},
+    MediaClient, MediaClientExtCommand,
+};
+
+mod replay;
+
+#[cfg(test)]
+mod test;
+
+pub use replay::{replay, Divergence, ReplayReport};
+
+/// `MediaClientCommand` without the channels, which cannot be written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordedCommand {
+    InitFlooding,
+    RemoveSender(NodeId),
+    /// the `Sender` is replaced by a new channel on replay
+    AddSender(NodeId),
+    GetServerList,
+    AskServerType(NodeId),
+    AskFilesList(NodeId),
+    AskForFile(NodeId, String),
+}
+
+impl From<&MediaClientCommand> for RecordedCommand {
+    fn from(command: &MediaClientCommand) -> Self {
+        match command {
+            MediaClientCommand::InitFlooding => RecordedCommand::InitFlooding,
+            MediaClientCommand::RemoveSender(id) => RecordedCommand::RemoveSender(*id),
+            MediaClientCommand::AddSender(id, _) => RecordedCommand::AddSender(*id),
+            MediaClientCommand::GetServerList => RecordedCommand::GetServerList,
+            MediaClientCommand::AskServerType(id) => RecordedCommand::AskServerType(*id),
+            MediaClientCommand::AskFilesList(id) => RecordedCommand::AskFilesList(*id),
+            MediaClientCommand::AskForFile(id, file_id) => {
+                RecordedCommand::AskForFile(*id, file_id.clone())
+            }
+        }
+    }
+}
+
+impl RecordedCommand {
+    /// `sender` is only used by `AddSender`
+    pub fn into_command(self, sender: Sender<Packet>) -> MediaClientCommand {
+        match self {
+            RecordedCommand::InitFlooding => MediaClientCommand::InitFlooding,
+            RecordedCommand::RemoveSender(id) => MediaClientCommand::RemoveSender(id),
+            RecordedCommand::AddSender(id) => MediaClientCommand::AddSender(id, sender),
+            RecordedCommand::GetServerList => MediaClientCommand::GetServerList,
+            RecordedCommand::AskServerType(id) => MediaClientCommand::AskServerType(id),
+            RecordedCommand::AskFilesList(id) => MediaClientCommand::AskFilesList(id),
+            RecordedCommand::AskForFile(id, file_id) => MediaClientCommand::AskForFile(id, file_id),
+        }
+    }
+}
+
+/// What happened to the client, in the order it was handled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TraceRecord {
+    /// `run` started and flooded the network
+    Start {
+        client_id: NodeId,
+        neighbours: Vec<NodeId>,
+        /// milliseconds since `UNIX_EPOCH`
+        started_at: u64,
+        /// seed of the random choices of the client
+        seed: u64,
+    },
+    PacketIn(Packet),
+    /// a packet as it was given to a neighbour, after the `FaultInjector`
+    PacketOut(Packet),
+    Command(RecordedCommand),
+    ExtCommand(MediaClientExtCommand),
+    /// `Debug` of the `MediaClientEvent`, which cannot be read back
+    Event(String),
+}
+
+impl TraceRecord {
+    /// `PacketOut` and `Event`, what a replay has to reproduce
+    pub fn is_output(&self) -> bool {
+        matches!(self, TraceRecord::PacketOut(_) | TraceRecord::Event(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// milliseconds since the recorder was given to the client, on the client's `Clock`
+    pub at: u64,
+    pub record: TraceRecord,
+}
+
+/// Writes a `TraceEntry` per line, as JSON
+pub struct TraceRecorder {
+    writer: RefCell<Box<dyn Write + Send>>,
+    clock: Arc<dyn Clock>,
+    created: Instant,
+}
+
+impl TraceRecorder {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        let clock = Arc::new(SystemClock);
+        Self {
+            writer: RefCell::new(Box::new(writer)),
+            created: clock.now(),
+            clock,
+        }
+    }
+    /// Replaces the `SystemClock` giving the `at` of the entries, which start again from zero
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.created = clock.now();
+        self.clock = clock;
+        self
+    }
+    /// Records in the file at `path`, which is truncated
+    ///
+    /// # Errors
+    /// if the file cannot be created
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(LineWriter::new(File::create(path)?)))
+    }
+    pub fn record(&self, record: TraceRecord) {
+        let entry = TraceEntry {
+            at: self
+                .clock
+                .now()
+                .saturating_duration_since(self.created)
+                .as_millis()
+                .try_into()
+                .unwrap_or(u64::MAX),
+            record,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.writer.borrow_mut(), "{line}"));
+        let _ = result.inspect_err(|e| {
+            error!("[mediaclient] error writing trace {e}");
+        });
+    }
+}
+
+impl MediaClient {
+    /// Records the `Start`, right before the first flood
+    pub(super) fn start_trace(&self) {
+        self.record(|| {
+            let mut neighbours = self.packet_send.keys().copied().collect::<Vec<NodeId>>();
+            neighbours.sort_unstable();
+            TraceRecord::Start {
+                client_id: self.id,
+                neighbours,
+                started_at: self.clock.unix_millis(),
+                seed: self.seed,
+            }
+        });
+    }
+    /// Does nothing without a `TraceRecorder`, `record` is only called with one
+    pub(super) fn record(&self, record: impl FnOnce() -> TraceRecord) {
+        if let Some(trace) = &self.trace {
+            trace.record(record());
+        }
+    }
+}
+
+/// Reads the entries written by a `TraceRecorder`
+///
+/// # Errors
+/// if `reader` fails or a line is not a `TraceEntry`
+pub fn read_trace(reader: impl BufRead) -> io::Result<Vec<TraceEntry>> {
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
+
+/// `Write` into a buffer that can still be read while a `TraceRecorder` owns it
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> Vec<u8> {
+        self.0
+            .lock()
+            .map(|buffer| buffer.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("poisoned trace buffer"))?
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use wg_2024::packet::Packet;
+
+use super::{read_trace, SharedBuffer, TraceEntry, TraceRecord, TraceRecorder};
+use crate::media_client::{Clock, ManualClock, MediaClient, MediaClientConfig};
+
+/// First output of a replay that is not the recorded one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// position among the outputs
+    pub index: usize,
+    /// `None` if the replay produced more outputs than the trace
+    pub recorded: Option<TraceRecord>,
+    /// `None` if the replay produced fewer outputs than the trace
+    pub replayed: Option<TraceRecord>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    /// `PacketOut` and `Event` records produced by the replay
+    pub outputs: Vec<TraceRecord>,
+    /// `None` if the outputs are the recorded ones
+    pub divergence: Option<Divergence>,
+}
+
+/// Feeds the packets and commands of a trace to a new `MediaClient`, in the order they were
+/// handled and without waiting between them, and compares its outputs with the recorded ones.
+///
+/// The `clock` and `seed` of `config` are replaced: the client gets the recorded seed and
+/// a `ManualClock` moved to the `at` of each entry, firing the delayed `Ack`s and giving up
+/// the partial documents on the way like `run` does.
+/// `config` should point to an empty directory: a `TextCache` left by the recorded run
+/// would answer some `AskForFile` without sending anything.
+///
+/// # Returns
+/// `None` if the trace has no `Start` record
+pub fn replay(entries: &[TraceEntry], config: MediaClientConfig) -> Option<ReplayReport> {
+    let (client_id, neighbours, started_at, seed, start_at) =
+        entries.iter().find_map(|entry| match &entry.record {
+            TraceRecord::Start {
+                client_id,
+                neighbours,
+                started_at,
+                seed,
+            } => Some((*client_id, neighbours.clone(), *started_at, *seed, entry.at)),
+            _ => None,
+        })?;
+    // the time of the recorder is `started_at` when it reaches the `Start`
+    let clock = Arc::new(ManualClock::new(
+        UNIX_EPOCH + Duration::from_millis(started_at.saturating_sub(start_at)),
+    ));
+    let created = clock.now();
+    let config = MediaClientConfig {
+        clock: clock.clone(),
+        seed: Some(seed),
+        ..config
+    };
+    // every receiver is kept so that nothing sent by the client fails
+    let mut receivers: Vec<Receiver<Packet>> = Vec::new();
+    let mut new_sender = || -> Sender<Packet> {
+        let (sender, receiver) = unbounded();
+        receivers.push(receiver);
+        sender
+    };
+    let packet_send = neighbours.iter().map(|id| (*id, new_sender())).collect();
+    let (event_send, _event_recv) = unbounded();
+    let buffer = SharedBuffer::default();
+    let mut client = MediaClient::new(
+        client_id,
+        event_send,
+        unbounded().1,
+        unbounded().1,
+        packet_send,
+        config,
+    )
+    .with_trace_recorder(TraceRecorder::new(buffer.clone()));
+
+    for entry in entries {
+        advance(
+            &mut client,
+            &clock,
+            created + Duration::from_millis(entry.at),
+        );
+        match &entry.record {
+            TraceRecord::Start { .. } => {
+                client.start_trace();
+                client.flood_network();
+            }
+            TraceRecord::PacketIn(packet) => client.handle_packet(packet.clone()),
+            TraceRecord::Command(command) => {
+                client.handle_command(command.clone().into_command(new_sender()));
+            }
+            TraceRecord::ExtCommand(command) => client.handle_ext_command(command.clone()),
+            TraceRecord::PacketOut(_) | TraceRecord::Event(_) => (),
+        }
+    }
+    drop(client);
+
+    let outputs = read_trace(buffer.contents().as_slice())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| entry.record)
+        .filter(TraceRecord::is_output)
+        .collect::<Vec<TraceRecord>>();
+    let recorded = entries
+        .iter()
+        .map(|entry| &entry.record)
+        .filter(|record| record.is_output())
+        .collect::<Vec<&TraceRecord>>();
+    let divergence = (0..recorded.len().max(outputs.len()))
+        .find(|&i| recorded.get(i).copied() != outputs.get(i))
+        .map(|index| Divergence {
+            index,
+            recorded: recorded.get(index).copied().cloned(),
+            replayed: outputs.get(index).cloned(),
+        });
+    Some(ReplayReport {
+        outputs,
+        divergence,
+    })
+}
+
+/// Moves `clock` to `target`, firing on the way the timers of `run`
+fn advance(client: &mut MediaClient, clock: &ManualClock, target: Instant) {
+    while let Some(due) = [client.faults.next_due(), client.renderer.next_give_up()]
+        .into_iter()
+        .flatten()
+        .min()
+        .filter(|due| *due <= target)
+    {
+        clock.advance(due.saturating_duration_since(clock.now()));
+        client.send_delayed_acks(due);
+        client.give_up_partial_documents();
+    }
+    clock.advance(target.saturating_duration_since(clock.now()));
+}
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use assembler::HighLevelMessageFactory;
+use crossbeam_channel::unbounded;
+use messages::high_level_messages::{MessageContent::FromServer, ServerMessage, ServerType};
+use wg_2024::{
+    network::SourceRoutingHeader,
+    packet::{FloodResponse, Fragment, NodeType, PacketType, FRAGMENT_DSIZE},
+};
+
+use super::*;
+use crate::media_client::{
+    test_util::test_config, Clock, FaultConfig, ManualClock, MediaClientConfig,
+    MediaClientExtCommand, SystemClock,
+};
+
+const CLIENT: NodeId = 1;
+const DRONE: NodeId = 11;
+const SERVER: NodeId = 21;
+
+/// Runs a client by hand through a flood and a `GetServerType` answered by `SERVER`,
+/// with faults drawn from `seed`
+fn record_session(name: &str, seed: Option<u64>) -> Vec<TraceEntry> {
+    let buffer = SharedBuffer::default();
+    let clock = Arc::new(ManualClock::default());
+    let (drone_send, drone_recv) = unbounded();
+    let (event_send, _event_recv) = unbounded();
+    let mut client = MediaClient::new(
+        CLIENT,
+        event_send,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(DRONE, drone_send)]),
+        MediaClientConfig {
+            clock: clock.clone(),
+            seed,
+            ..test_config(name)
+        },
+    )
+    .with_trace_recorder(TraceRecorder::new(buffer.clone()));
+
+    clock.advance(Duration::from_millis(300));
+    client.start_trace();
+    client.flood_network();
+    client.handle_ext_command(MediaClientExtCommand::SetFaults(FaultConfig {
+        ack_delay: Duration::from_millis(100),
+        corrupt_fragments: 0.5,
+        duplicate_packets: 0.5,
+        ..FaultConfig::default()
+    }));
+    let path_trace = vec![
+        (CLIENT, NodeType::Client),
+        (DRONE, NodeType::Drone),
+        (SERVER, NodeType::Server),
+    ];
+    client.handle_packet(Packet {
+        routing_header: SourceRoutingHeader::new(vec![SERVER, DRONE, CLIENT], 2),
+        session_id: 1,
+        pack_type: PacketType::FloodResponse(FloodResponse {
+            flood_id: 1,
+            path_trace,
+        }),
+    });
+    assert!(drone_recv
+        .try_iter()
+        .any(|packet| matches!(packet.pack_type, PacketType::MsgFragment(_))));
+
+    let mut server = HighLevelMessageFactory::new(SERVER, NodeType::Server);
+    for mut fragment in server.get_message_from_message_content(
+        FromServer(ServerMessage::ServerType(ServerType::Text)),
+        &SourceRoutingHeader::with_first_hop(vec![SERVER, DRONE, CLIENT]),
+        CLIENT,
+    ) {
+        fragment.routing_header.hop_index = 2;
+        client.handle_packet(fragment);
+        clock.advance(Duration::from_millis(30));
+    }
+    // what `run` does once the delayed `Ack`s are due
+    while let Some(due) = client.faults.next_due() {
+        clock.advance(due.saturating_duration_since(clock.now()));
+        client.send_delayed_acks(due);
+    }
+    client.handle_command(MediaClientCommand::GetServerList);
+    drop(client);
+
+    read_trace(buffer.contents().as_slice()).unwrap()
+}
+
+#[test]
+fn test_recorded_trace_is_read_back() {
+    let entries = record_session("trace_read_back", Some(0));
+
+    assert!(matches!(
+        entries[0].record,
+        TraceRecord::Start {
+            client_id: CLIENT,
+            ..
+        }
+    ));
+    assert!(entries
+        .iter()
+        .any(|entry| entry.record == TraceRecord::Command(RecordedCommand::GetServerList)));
+    assert!(entries.iter().any(|entry| matches!(
+        &entry.record,
+        TraceRecord::Event(event) if event.starts_with("ReceveidServerType")
+    )));
+    assert!(entries.windows(2).all(|pair| pair[0].at <= pair[1].at));
+}
+
+#[test]
+fn test_replay_reproduces_the_recorded_outputs() {
+    let entries = record_session("trace_record", Some(0));
+
+    let report = replay(&entries, test_config("trace_replay")).unwrap();
+    assert_eq!(report.divergence, None);
+    assert_eq!(
+        report.outputs.len(),
+        entries
+            .iter()
+            .filter(|entry| entry.record.is_output())
+            .count()
+    );
+}
+
+#[test]
+fn test_replay_uses_the_recorded_seed_and_time() {
+    let entries = record_session("trace_seed_record", None);
+    let Some(TraceRecord::Start { seed, .. }) = entries.first().map(|entry| &entry.record) else {
+        panic!("no start");
+    };
+    assert!(entries
+        .iter()
+        .any(|entry| matches!(entry.record, TraceRecord::ExtCommand(_))));
+
+    let report = replay(
+        &entries,
+        MediaClientConfig {
+            seed: Some(seed.wrapping_add(1)),
+            clock: Arc::new(SystemClock),
+            ..test_config("trace_seed_replay")
+        },
+    )
+    .unwrap();
+    assert_eq!(report.divergence, None);
+}
+
+#[test]
+fn test_replay_reports_the_first_divergence() {
+    let mut entries = record_session("trace_diverge_record", Some(0));
+    let last_fragment = entries
+        .iter()
+        .rposition(|entry| {
+            matches!(
+                &entry.record,
+                TraceRecord::PacketIn(packet) if matches!(packet.pack_type, PacketType::MsgFragment(_))
+            )
+        })
+        .unwrap();
+    entries.remove(last_fragment);
+
+    let report = replay(&entries, test_config("trace_diverge_replay")).unwrap();
+    let divergence = report.divergence.unwrap();
+    // the `Ack` of the missing fragment would only be sent once due
+    assert!(matches!(
+        divergence.recorded,
+        Some(TraceRecord::Event(event)) if event.starts_with("ReceveidServerType")
+    ));
+    assert!(replay(&entries[1..], test_config("trace_diverge_no_start")).is_none());
+}
+
+#[test]
+fn test_recorder_uses_the_client_clock_and_records_what_is_sent() {
+    let buffer = SharedBuffer::default();
+    let clock = Arc::new(ManualClock::default());
+    let mut client = MediaClient::new(
+        CLIENT,
+        unbounded().0,
+        unbounded().1,
+        unbounded().1,
+        HashMap::from([(DRONE, unbounded().0)]),
+        MediaClientConfig {
+            clock: clock.clone(),
+            ..test_config("trace_sent")
+        },
+    )
+    .with_trace_recorder(TraceRecorder::new(buffer.clone()));
+    client.handle_ext_command(MediaClientExtCommand::SetFaults(FaultConfig {
+        drop_fragments: 1.0,
+        duplicate_packets: 1.0,
+        ..FaultConfig::default()
+    }));
+
+    clock.advance(Duration::from_secs(5));
+    let header = SourceRoutingHeader::with_first_hop(vec![CLIENT, DRONE, SERVER]);
+    client.send_packet(Packet::new_ack(header.clone(), 3, 0), None);
+    client.send_packet(
+        Packet::new_fragment(
+            header,
+            3,
+            Fragment {
+                fragment_index: 0,
+                total_n_fragments: 1,
+                length: 0,
+                data: [0; FRAGMENT_DSIZE],
+            },
+        ),
+        None,
+    );
+    drop(client);
+
+    let sent = read_trace(buffer.contents().as_slice())
+        .unwrap()
+        .into_iter()
+        .filter_map(|entry| match entry.record {
+            TraceRecord::PacketOut(packet) => Some((entry.at, packet.pack_type)),
+            _ => None,
+        })
+        .collect::<Vec<(u64, PacketType)>>();
+    assert_eq!(sent.len(), 2);
+    assert!(sent
+        .iter()
+        .all(|(at, pack_type)| *at == 5_000 && matches!(pack_type, PacketType::Ack(_))));
+}
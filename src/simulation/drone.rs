@@ -0,0 +1,203 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crossbeam_channel::{select_biased, Receiver, Sender};
+use log::info;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use wg_2024::{
+    network::{NodeId, SourceRoutingHeader},
+    packet::{FloodRequest, FloodResponse, Nack, NackType, NodeType, Packet, PacketType},
+};
+
+use super::NodeCommand;
+
+/// Drone that follows the source routing protocol, drops fragments with probability `pdr`
+/// and sends the packets it cannot deliver to the simulation controller
+pub(super) struct MockDrone {
+    id: NodeId,
+    pdr: f32,
+    rng: StdRng,
+    neighbours: HashMap<NodeId, Sender<Packet>>,
+    /// `(initiator_id, flood_id)` of the floods already seen
+    floods: HashSet<(NodeId, u64)>,
+    dropped: Arc<AtomicUsize>,
+
+    command_recv: Receiver<NodeCommand>,
+    packet_recv: Receiver<Packet>,
+    shortcut_send: Sender<Packet>,
+}
+
+impl MockDrone {
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn new(
+        id: NodeId,
+        pdr: f32,
+        seed: u64,
+        neighbours: HashMap<NodeId, Sender<Packet>>,
+        dropped: Arc<AtomicUsize>,
+        command_recv: Receiver<NodeCommand>,
+        packet_recv: Receiver<Packet>,
+        shortcut_send: Sender<Packet>,
+    ) -> Self {
+        Self {
+            id,
+            pdr,
+            rng: StdRng::seed_from_u64(seed),
+            neighbours,
+            floods: HashSet::new(),
+            dropped,
+            command_recv,
+            packet_recv,
+            shortcut_send,
+        }
+    }
+    /// Returns when it crashes or when the simulation is dropped
+    pub(super) fn run(&mut self) {
+        loop {
+            select_biased! {
+                recv(self.command_recv) -> command => {
+                    match command {
+                        Ok(NodeCommand::Crash) | Err(_) => break,
+                        Ok(command) => self.handle_command(command),
+                    }
+                },
+                recv(self.packet_recv) -> packet => {
+                    if let Ok(packet) = packet {
+                        self.handle_packet(packet);
+                    }
+                }
+            }
+        }
+        info!("[MockDrone {}] crashed", self.id);
+    }
+    fn handle_command(&mut self, command: NodeCommand) {
+        match command {
+            NodeCommand::SetPacketDropRate(pdr) => self.pdr = pdr,
+            NodeCommand::AddSender(id, sender) => {
+                self.neighbours.insert(id, sender);
+            }
+            NodeCommand::RemoveSender(id) => {
+                self.neighbours.remove(&id);
+            }
+            NodeCommand::Crash => (),
+        }
+    }
+    fn handle_packet(&mut self, packet: Packet) {
+        if let PacketType::FloodRequest(request) = packet.pack_type {
+            self.handle_flood_request(request, packet.session_id);
+            return;
+        }
+        if packet.routing_header.current_hop() != Some(self.id) {
+            self.send_nack(&packet, NackType::UnexpectedRecipient(self.id));
+            return;
+        }
+        let mut forwarded = packet.clone();
+        forwarded.routing_header.increase_hop_index();
+        let Some(next_hop) = forwarded.routing_header.current_hop() else {
+            self.send_nack(&packet, NackType::DestinationIsDrone);
+            return;
+        };
+        let Some(sender) = self.neighbours.get(&next_hop) else {
+            self.send_nack(&packet, NackType::ErrorInRouting(next_hop));
+            return;
+        };
+        if matches!(packet.pack_type, PacketType::MsgFragment(_))
+            && self.rng.gen::<f32>() < self.pdr
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            self.send_nack(&packet, NackType::Dropped);
+            return;
+        }
+        if sender.send(forwarded).is_err() {
+            self.send_nack(&packet, NackType::ErrorInRouting(next_hop));
+        }
+    }
+    /// Only fragments are answered with a `Nack`, the other packets go through the controller
+    fn send_nack(&self, packet: &Packet, nack_type: NackType) {
+        let PacketType::MsgFragment(fragment) = &packet.pack_type else {
+            self.shortcut_send.send(packet.clone()).ok();
+            return;
+        };
+        let header = &packet.routing_header;
+        let hops = std::iter::once(self.id)
+            .chain(
+                header.hops[..header.hop_index.min(header.hops.len())]
+                    .iter()
+                    .rev()
+                    .copied(),
+            )
+            .collect::<Vec<NodeId>>();
+        let nack = Packet::new_nack(
+            SourceRoutingHeader::with_first_hop(hops),
+            packet.session_id,
+            Nack {
+                fragment_index: fragment.fragment_index,
+                nack_type,
+            },
+        );
+        self.send_back(nack);
+    }
+    fn handle_flood_request(&mut self, mut request: FloodRequest, session_id: u64) {
+        let previous = request.path_trace.last().map(|(id, _)| *id);
+        request.path_trace.push((self.id, NodeType::Drone));
+        let first_time = self.floods.insert((request.initiator_id, request.flood_id));
+        let next = self
+            .neighbours
+            .iter()
+            .filter(|(id, _)| Some(**id) != previous)
+            .map(|(_, sender)| sender)
+            .collect::<Vec<&Sender<Packet>>>();
+        if !first_time || next.is_empty() {
+            self.send_back(flood_response(request, session_id));
+            return;
+        }
+        let packet = Packet {
+            routing_header: SourceRoutingHeader::default(),
+            session_id,
+            pack_type: PacketType::FloodRequest(request),
+        };
+        for sender in next {
+            sender.send(packet.clone()).ok();
+        }
+    }
+    /// Sends a packet whose `hops` start with this drone
+    fn send_back(&self, packet: Packet) {
+        match packet
+            .routing_header
+            .current_hop()
+            .and_then(|id| self.neighbours.get(&id))
+        {
+            Some(sender) if sender.send(packet.clone()).is_ok() => (),
+            _ => {
+                self.shortcut_send.send(packet).ok();
+            }
+        }
+    }
+}
+
+/// Response going back along the `path_trace` of `request`,
+/// which has to end with the node answering
+pub(super) fn flood_response(request: FloodRequest, session_id: u64) -> Packet {
+    let mut hops = request
+        .path_trace
+        .iter()
+        .map(|(id, _)| *id)
+        .collect::<Vec<NodeId>>();
+    hops.reverse();
+    if hops.last() != Some(&request.initiator_id) {
+        hops.push(request.initiator_id);
+    }
+    Packet {
+        routing_header: SourceRoutingHeader::with_first_hop(hops),
+        session_id,
+        pack_type: PacketType::FloodResponse(FloodResponse {
+            flood_id: request.flood_id,
+            path_trace: request.path_trace,
+        }),
+    }
+}
@@ -0,0 +1,413 @@
+//! In-process network used to test the `MediaClient` end to end.
+//!
+//! Every node runs on its own thread and they are connected by crossbeam channels,
+//! like in the real simulation. The `Simulation` plays the role of the simulation
+//! controller: it delivers the packets that cannot travel through the network and
+//! forwards the events of the client to the test.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{select_biased, unbounded, Receiver, Sender};
+use messages::client_commands::{MediaClientCommand, MediaClientEvent};
+use wg_2024::{network::NodeId, packet::Packet};
+
+use crate::media_client::{
+    MediaClient, MediaClientConfig, MediaClientExtCommand, MediaClientExtEvent, ViewerLaunch,
+};
+
+mod drone;
+mod server;
+
+#[cfg(test)]
+mod test;
+
+pub use server::{ServerContent, StandInServer};
+
+use drone::MockDrone;
+
+/// Sent by the `Simulation` to the drones and the `StandInServer`s
+#[derive(Debug, Clone)]
+pub enum NodeCommand {
+    /// ignored by the servers
+    SetPacketDropRate(f32),
+    AddSender(NodeId, Sender<Packet>),
+    RemoveSender(NodeId),
+    Crash,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Drone { pdr: f32 },
+    Server(ServerContent),
+}
+
+/// Describes the network, `start` spawns it together with a `MediaClient`
+#[derive(Debug, Clone)]
+pub struct SimulationBuilder {
+    client_id: NodeId,
+    config: MediaClientConfig,
+    nodes: BTreeMap<NodeId, NodeKind>,
+    links: BTreeSet<(NodeId, NodeId)>,
+    seed: u64,
+}
+
+impl SimulationBuilder {
+    /// The client writes its documents in `output_root` and does not open them
+    #[must_use]
+    pub fn new(client_id: NodeId, output_root: PathBuf) -> Self {
+        Self {
+            client_id,
+            config: MediaClientConfig {
+                output_root,
+                viewer: ViewerLaunch::None,
+                ..MediaClientConfig::default()
+            },
+            nodes: BTreeMap::new(),
+            links: BTreeSet::new(),
+            seed: 0,
+        }
+    }
+    /// Replaces the whole configuration of the client
+    #[must_use]
+    pub fn with_config(mut self, config: MediaClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+    /// Drone dropping each fragment with probability `pdr`
+    #[must_use]
+    pub fn drone(mut self, id: NodeId, pdr: f32) -> Self {
+        self.nodes.insert(id, NodeKind::Drone { pdr });
+        self
+    }
+    /// `StandInServer` answering with `content`
+    #[must_use]
+    pub fn server(mut self, id: NodeId, content: ServerContent) -> Self {
+        self.nodes.insert(id, NodeKind::Server(content));
+        self
+    }
+    #[must_use]
+    pub fn text_server<F, C>(self, id: NodeId, files: impl IntoIterator<Item = (F, C)>) -> Self
+    where
+        F: Into<String>,
+        C: Into<String>,
+    {
+        let files = files
+            .into_iter()
+            .map(|(file_id, html)| (file_id.into(), html.into()))
+            .collect();
+        self.server(id, ServerContent::Text(files))
+    }
+    /// `media` are `(media_id, base64 content)`
+    #[must_use]
+    pub fn media_server<M, C>(self, id: NodeId, media: impl IntoIterator<Item = (M, C)>) -> Self
+    where
+        M: Into<String>,
+        C: Into<String>,
+    {
+        let media = media
+            .into_iter()
+            .map(|(media_id, content)| (media_id.into(), content.into()))
+            .collect();
+        self.server(id, ServerContent::Media(media))
+    }
+    /// Connects two nodes, either of them can be the client
+    #[must_use]
+    pub fn link(mut self, a: NodeId, b: NodeId) -> Self {
+        self.links.insert((a.min(b), a.max(b)));
+        self
+    }
+    /// Seed of the packet drops of the drones
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    /// Spawns every node and the client
+    ///
+    /// # Panics
+    /// if a link refers to a node that was not added
+    #[must_use]
+    pub fn start(self) -> Simulation {
+        let mut packet_channels = HashMap::new();
+        for &id in self.nodes.keys().chain([&self.client_id]) {
+            packet_channels.insert(id, unbounded::<Packet>());
+        }
+        let mut neighbours: HashMap<NodeId, BTreeSet<NodeId>> = HashMap::new();
+        for &(a, b) in &self.links {
+            assert!(
+                packet_channels.contains_key(&a) && packet_channels.contains_key(&b),
+                "link ({a}, {b}) refers to an unknown node"
+            );
+            neighbours.entry(a).or_default().insert(b);
+            neighbours.entry(b).or_default().insert(a);
+        }
+        let senders_of = |id: NodeId| {
+            neighbours
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .map(|n| (*n, packet_channels[n].0.clone()))
+                .collect::<HashMap<NodeId, Sender<Packet>>>()
+        };
+
+        let (shortcut_send, shortcut_recv) = unbounded();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let mut node_commands = HashMap::new();
+        let mut handles = Vec::new();
+        for (&id, kind) in &self.nodes {
+            let (command_send, command_recv) = unbounded();
+            node_commands.insert(id, command_send);
+            let packet_recv = packet_channels[&id].1.clone();
+            let shortcut_send = shortcut_send.clone();
+            let neighbours = senders_of(id);
+            let handle = match kind.clone() {
+                NodeKind::Drone { pdr } => {
+                    let mut drone = MockDrone::new(
+                        id,
+                        pdr,
+                        self.seed ^ u64::from(id),
+                        neighbours,
+                        dropped.clone(),
+                        command_recv,
+                        packet_recv,
+                        shortcut_send,
+                    );
+                    thread::spawn(move || drone.run())
+                }
+                NodeKind::Server(content) => {
+                    let mut server = StandInServer::new(id, content, packet_recv, neighbours)
+                        .with_controller(command_recv, shortcut_send);
+                    thread::spawn(move || server.run())
+                }
+            };
+            handles.push(handle);
+        }
+        drop(shortcut_send);
+
+        let (client_command_send, client_command_recv) = unbounded();
+        let (client_event_send, client_event_recv) = unbounded();
+        let (ext_command_send, ext_command_recv) = unbounded();
+        let (ext_event_send, ext_event_recv) = unbounded();
+        let mut client = MediaClient::new(
+            self.client_id,
+            client_event_send,
+            client_command_recv,
+            packet_channels[&self.client_id].1.clone(),
+            senders_of(self.client_id),
+            self.config,
+        )
+        .with_ext_controller(ext_event_send, ext_command_recv);
+        let client_handle = thread::spawn(move || client.run());
+
+        let (event_send, event_recv) = unbounded();
+        let mut controller = Controller {
+            packet_send: packet_channels
+                .iter()
+                .map(|(id, (sender, _))| (*id, sender.clone()))
+                .collect(),
+            client_event_recv,
+            shortcut_recv,
+            event_send,
+        };
+        let controller_handle = thread::spawn(move || controller.run());
+
+        Simulation {
+            client_id: self.client_id,
+            neighbours,
+            packet_send: packet_channels
+                .into_iter()
+                .map(|(id, (sender, _))| (id, sender))
+                .collect(),
+            dropped,
+            command_send: Some(client_command_send),
+            event_recv,
+            ext_command_send,
+            ext_event_recv,
+            node_commands,
+            client_handle: Some(client_handle),
+            controller_handle: Some(controller_handle),
+            handles,
+        }
+    }
+}
+
+/// Delivers the shortcut packets and forwards the events of the client to the test
+struct Controller {
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    client_event_recv: Receiver<MediaClientEvent>,
+    shortcut_recv: Receiver<Packet>,
+    event_send: Sender<MediaClientEvent>,
+}
+
+impl Controller {
+    /// Returns once the client and every node stopped
+    fn run(&mut self) {
+        loop {
+            select_biased! {
+                recv(self.client_event_recv) -> event => {
+                    let Ok(event) = event else {
+                        break;
+                    };
+                    if let MediaClientEvent::ControllerShortcut(packet) = &event {
+                        self.shortcut(packet.clone());
+                    }
+                    self.event_send.send(event).ok();
+                },
+                recv(self.shortcut_recv) -> packet => {
+                    match packet {
+                        Ok(packet) => self.shortcut(packet),
+                        Err(_) => self.shortcut_recv = crossbeam_channel::never(),
+                    }
+                }
+            }
+        }
+    }
+    fn shortcut(&self, mut packet: Packet) {
+        let Some(destination) = packet.routing_header.destination() else {
+            return;
+        };
+        packet.routing_header.hop_index = packet.routing_header.hops.len() - 1;
+        if let Some(sender) = self.packet_send.get(&destination) {
+            sender.send(packet).ok();
+        }
+    }
+}
+
+/// Network running on background threads, stopped when dropped
+pub struct Simulation {
+    client_id: NodeId,
+    neighbours: HashMap<NodeId, BTreeSet<NodeId>>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    dropped: Arc<AtomicUsize>,
+
+    command_send: Option<Sender<MediaClientCommand>>,
+    event_recv: Receiver<MediaClientEvent>,
+    ext_command_send: Sender<MediaClientExtCommand>,
+    ext_event_recv: Receiver<MediaClientExtEvent>,
+    node_commands: HashMap<NodeId, Sender<NodeCommand>>,
+
+    client_handle: Option<JoinHandle<()>>,
+    controller_handle: Option<JoinHandle<()>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Simulation {
+    pub fn client_id(&self) -> NodeId {
+        self.client_id
+    }
+    pub fn send_command(&self, command: MediaClientCommand) {
+        if let Some(command_send) = &self.command_send {
+            command_send.send(command).ok();
+        }
+    }
+    pub fn send_ext_command(&self, command: MediaClientExtCommand) {
+        self.ext_command_send.send(command).ok();
+    }
+    /// Every `MediaClientEvent`, `ControllerShortcut`s included, in the order they were sent
+    pub fn events(&self) -> &Receiver<MediaClientEvent> {
+        &self.event_recv
+    }
+    pub fn ext_events(&self) -> &Receiver<MediaClientExtEvent> {
+        &self.ext_event_recv
+    }
+    /// Waits for the first event for which `f` returns `Some`, the events before it are discarded
+    ///
+    /// # Returns
+    /// `None` if `timeout` expired
+    pub fn wait_event<T>(
+        &self,
+        timeout: Duration,
+        f: impl FnMut(MediaClientEvent) -> Option<T>,
+    ) -> Option<T> {
+        wait_for(&self.event_recv, timeout, f)
+    }
+    /// Like `wait_event` for the `MediaClientExtEvent`s
+    pub fn wait_ext_event<T>(
+        &self,
+        timeout: Duration,
+        f: impl FnMut(MediaClientExtEvent) -> Option<T>,
+    ) -> Option<T> {
+        wait_for(&self.ext_event_recv, timeout, f)
+    }
+    /// Changes the probability that the drone `id` drops a fragment
+    pub fn set_packet_drop_rate(&self, id: NodeId, pdr: f32) {
+        if let Some(command_send) = self.node_commands.get(&id) {
+            command_send.send(NodeCommand::SetPacketDropRate(pdr)).ok();
+        }
+    }
+    /// Disconnects the drone `id` from its neighbours, the client included, and stops it
+    pub fn crash_drone(&mut self, id: NodeId) {
+        for neighbour in self.neighbours.remove(&id).unwrap_or_default() {
+            if let Some(links) = self.neighbours.get_mut(&neighbour) {
+                links.remove(&id);
+            }
+            if neighbour == self.client_id {
+                self.send_command(MediaClientCommand::RemoveSender(id));
+            } else if let Some(command_send) = self.node_commands.get(&neighbour) {
+                command_send.send(NodeCommand::RemoveSender(id)).ok();
+            }
+        }
+        if let Some(command_send) = self.node_commands.remove(&id) {
+            command_send.send(NodeCommand::Crash).ok();
+        }
+    }
+    /// Connects two running nodes
+    pub fn add_link(&mut self, a: NodeId, b: NodeId) {
+        let (Some(a_send), Some(b_send)) = (self.packet_send.get(&a), self.packet_send.get(&b))
+        else {
+            return;
+        };
+        let (a_send, b_send) = (a_send.clone(), b_send.clone());
+        for (from, to, sender) in [(a, b, b_send), (b, a, a_send)] {
+            self.neighbours.entry(from).or_default().insert(to);
+            if from == self.client_id {
+                self.send_command(MediaClientCommand::AddSender(to, sender));
+            } else if let Some(command_send) = self.node_commands.get(&from) {
+                command_send.send(NodeCommand::AddSender(to, sender)).ok();
+            }
+        }
+    }
+    /// Fragments dropped by every drone since the start
+    pub fn dropped_fragments(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        self.command_send = None;
+        if let Some(handle) = self.client_handle.take() {
+            handle.join().ok();
+        }
+        self.node_commands.clear();
+        for handle in self.handles.drain(..) {
+            handle.join().ok();
+        }
+        if let Some(handle) = self.controller_handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+fn wait_for<E, T>(
+    recv: &Receiver<E>,
+    timeout: Duration,
+    mut f: impl FnMut(E) -> Option<T>,
+) -> Option<T> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let event = recv.recv_deadline(deadline).ok()?;
+        if let Some(found) = f(event) {
+            return Some(found);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use assembler::HighLevelMessageFactory;
+use base64::{engine::general_purpose, Engine};
+use crossbeam_channel::{never, select_biased, Receiver, Sender};
+use log::{error, info, warn};
+use messages::high_level_messages::{
+    ClientMessage, Message,
+    MessageContent::{FromClient, FromServer},
+    ServerMessage, ServerType,
+};
+use source_routing::Router;
+use wg_2024::{
+    network::{NodeId, SourceRoutingHeader},
+    packet::{FloodRequest, FloodResponse, Nack, NackType, NodeType, Packet, PacketType},
+};
+
+use super::{drone::flood_response, NodeCommand};
+
+/// extensions of the files served by `ServerContent::text_from_dir`
+const TEXT_EXTENSIONS: [&str; 3] = ["html", "htm", "txt"];
+
+/// What a `StandInServer` answers with
+#[derive(Debug, Clone)]
+pub enum ServerContent {
+    /// `file_id` -> html
+    Text(BTreeMap<String, String>),
+    /// `media_id` -> base64 content
+    Media(BTreeMap<String, String>),
+}
+
+impl ServerContent {
+    /// Text files of `dir` and its subdirectories, the `file_id` is the path relative to `dir`.
+    /// Entries whose name starts with `.` are left out.
+    ///
+    /// # Errors
+    /// if `dir` cannot be read
+    pub fn text_from_dir(dir: &Path) -> io::Result<Self> {
+        let mut files = BTreeMap::new();
+        for (id, path) in files_in(dir)? {
+            if !is_text(&path) {
+                continue;
+            }
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    files.insert(id, content);
+                }
+                Err(e) => error!("[StandInServer] error reading {}: {e}", path.display()),
+            }
+        }
+        Ok(ServerContent::Text(files))
+    }
+    /// Every other file of `dir` and its subdirectories, the `media_id` is the path
+    /// relative to `dir`, which is what an `<img src>` in a text file of `dir` refers to.
+    ///
+    /// # Errors
+    /// if `dir` cannot be read
+    pub fn media_from_dir(dir: &Path) -> io::Result<Self> {
+        let mut media = BTreeMap::new();
+        for (id, path) in files_in(dir)? {
+            if is_text(&path) {
+                continue;
+            }
+            match fs::read(&path) {
+                Ok(content) => {
+                    media.insert(id, general_purpose::STANDARD.encode(content));
+                }
+                Err(e) => error!("[StandInServer] error reading {}: {e}", path.display()),
+            }
+        }
+        Ok(ServerContent::Media(media))
+    }
+    fn server_type(&self) -> ServerType {
+        match self {
+            ServerContent::Text(_) => ServerType::Text,
+            ServerContent::Media(_) => ServerType::Media,
+        }
+    }
+    fn answer(&self, request: ClientMessage) -> Option<ServerMessage> {
+        match (self, request) {
+            (_, ClientMessage::GetServerType) => {
+                Some(ServerMessage::ServerType(self.server_type()))
+            }
+            (ServerContent::Text(files), ClientMessage::GetFilesList) => {
+                Some(ServerMessage::FilesList(files.keys().cloned().collect()))
+            }
+            (ServerContent::Text(files), ClientMessage::GetFile(file_id)) => {
+                let content = files.get(&file_id)?.clone();
+                Some(ServerMessage::File {
+                    file_id,
+                    size: content.len(),
+                    content,
+                })
+            }
+            (ServerContent::Media(media), ClientMessage::GetMedia(media_id)) => {
+                let content = media.get(&media_id)?.clone();
+                Some(ServerMessage::Media(media_id, content))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Server answering `GetServerType`, `GetFilesList`, `GetFile` and `GetMedia`
+/// with a fixed `ServerContent`, fragmented by the `HighLevelMessageFactory`.
+///
+/// Answers go back along the path of the request, or along another path learned
+/// from the `FloodRequest`s it received if a drone on it crashed.
+pub struct StandInServer {
+    id: NodeId,
+    content: ServerContent,
+    message_factory: HighLevelMessageFactory,
+    router: Router,
+    neighbours: HashMap<NodeId, Sender<Packet>>,
+    /// fragments waiting for an `Ack`
+    sent: HashMap<(u64, u64), Packet>,
+
+    command_recv: Receiver<NodeCommand>,
+    packet_recv: Receiver<Packet>,
+    /// where the `Ack`s, `Nack`s and `FloodResponse`s that cannot be sent go
+    shortcut_send: Option<Sender<Packet>>,
+}
+
+impl StandInServer {
+    #[must_use]
+    pub fn new(
+        id: NodeId,
+        content: ServerContent,
+        packet_recv: Receiver<Packet>,
+        neighbours: HashMap<NodeId, Sender<Packet>>,
+    ) -> Self {
+        let mut router = Router::new(id, NodeType::Server);
+        for neighbour in neighbours.keys() {
+            router.add_neighbour(*neighbour);
+        }
+        Self {
+            id,
+            content,
+            message_factory: HighLevelMessageFactory::new(id, NodeType::Server),
+            router,
+            neighbours,
+            sent: HashMap::new(),
+            command_recv: never(),
+            packet_recv,
+            shortcut_send: None,
+        }
+    }
+    /// Connects the channel of the `NodeCommand`s and the one of the packets
+    /// to deliver without going through the network
+    #[must_use]
+    pub fn with_controller(
+        mut self,
+        command_recv: Receiver<NodeCommand>,
+        shortcut_send: Sender<Packet>,
+    ) -> Self {
+        self.command_recv = command_recv;
+        self.shortcut_send = Some(shortcut_send);
+        self
+    }
+    /// Returns when the command channel or the packet channel is disconnected
+    pub fn run(&mut self) {
+        loop {
+            select_biased! {
+                recv(self.command_recv) -> command => {
+                    match command {
+                        Ok(NodeCommand::Crash) | Err(_) => break,
+                        Ok(command) => self.handle_command(command),
+                    }
+                },
+                recv(self.packet_recv) -> packet => {
+                    match packet {
+                        Ok(packet) => self.handle_packet(packet),
+                        Err(_) => break,
+                    }
+                }
+            }
+        }
+    }
+    fn handle_command(&mut self, command: NodeCommand) {
+        match command {
+            NodeCommand::AddSender(id, sender) => {
+                self.neighbours.insert(id, sender);
+                self.router.add_neighbour(id);
+            }
+            NodeCommand::RemoveSender(id) => {
+                self.neighbours.remove(&id);
+                self.router.remove_neighbour(id);
+            }
+            NodeCommand::SetPacketDropRate(_) | NodeCommand::Crash => (),
+        }
+    }
+    fn handle_packet(&mut self, packet: Packet) {
+        match packet.pack_type {
+            PacketType::MsgFragment(fragment) => {
+                let hops = &packet.routing_header.hops;
+                let Some(&source) = hops.first() else {
+                    return;
+                };
+                let mut back = hops.clone();
+                back.reverse();
+                self.send(Packet::new_ack(
+                    SourceRoutingHeader::with_first_hop(back),
+                    packet.session_id,
+                    fragment.fragment_index,
+                ));
+                if let Some(message) =
+                    self.message_factory
+                        .received_fragment(fragment, packet.session_id, source)
+                {
+                    self.handle_message(message, &packet.routing_header);
+                }
+            }
+            PacketType::Ack(ack) => {
+                self.sent.remove(&(packet.session_id, ack.fragment_index));
+            }
+            PacketType::Nack(nack) => self.handle_nack(nack, packet.session_id),
+            PacketType::FloodRequest(request) => {
+                self.handle_flood_request(request, packet.session_id)
+            }
+            PacketType::FloodResponse(response) => self.router.handle_flood_response(&response),
+        }
+    }
+    fn handle_message(&mut self, message: Message, request_header: &SourceRoutingHeader) {
+        let FromClient(request) = message.content else {
+            return;
+        };
+        info!("[StandInServer {}] received {request:?}", self.id);
+        let Some(answer) = self.content.answer(request.clone()) else {
+            warn!("[StandInServer {}] cannot answer {request:?}", self.id);
+            return;
+        };
+        let mut hops = request_header.hops.clone();
+        hops.reverse();
+        let header = SourceRoutingHeader::with_first_hop(hops);
+        for fragment in self.message_factory.get_message_from_message_content(
+            FromServer(answer),
+            &header,
+            message.source_id,
+        ) {
+            self.sent.insert(
+                (fragment.session_id, fragment.get_fragment_index()),
+                fragment.clone(),
+            );
+            self.send(fragment);
+        }
+    }
+    fn handle_nack(&mut self, nack: Nack, session_id: u64) {
+        let Some(mut packet) = self.sent.get(&(session_id, nack.fragment_index)).cloned() else {
+            return;
+        };
+        if let NackType::ErrorInRouting(crashed_id) = nack.nack_type {
+            self.router.drone_crashed(crashed_id);
+            let destination = packet.routing_header.destination();
+            if let Some(header) =
+                destination.and_then(|id| self.router.get_source_routing_header(id).ok())
+            {
+                packet.routing_header = header;
+                self.sent
+                    .insert((session_id, nack.fragment_index), packet.clone());
+            }
+        }
+        self.send(packet);
+    }
+    /// Answers every flood and learns the path it took
+    fn handle_flood_request(&mut self, mut request: FloodRequest, session_id: u64) {
+        request.path_trace.push((self.id, NodeType::Server));
+        self.router.handle_flood_response(&FloodResponse {
+            flood_id: request.flood_id,
+            path_trace: request.path_trace.clone(),
+        });
+        self.send(flood_response(request, session_id));
+    }
+    fn send(&self, packet: Packet) {
+        match packet
+            .routing_header
+            .current_hop()
+            .and_then(|id| self.neighbours.get(&id))
+        {
+            Some(sender) if sender.send(packet.clone()).is_ok() => (),
+            _ => match &self.shortcut_send {
+                Some(shortcut_send) if !matches!(packet.pack_type, PacketType::MsgFragment(_)) => {
+                    shortcut_send.send(packet).ok();
+                }
+                _ => warn!("[StandInServer {}] cannot send {packet}", self.id),
+            },
+        }
+    }
+}
+
+fn is_text(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| TEXT_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+/// Files under `dir` as `(path relative to dir with '/' separators, path)`
+fn files_in(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(String::new(), dir.to_path_buf())];
+    while let Some((prefix, dir)) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let id = format!("{prefix}{name}");
+            if entry.file_type()?.is_dir() {
+                dirs.push((format!("{id}/"), entry.path()));
+            } else {
+                files.push((id, entry.path()));
+            }
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,179 @@
+use std::{fs, io::Cursor};
+
+use base64::{engine::general_purpose, Engine};
+use image::{DynamicImage, ImageFormat};
+use messages::high_level_messages::ServerType;
+
+use super::*;
+use crate::media_client::{test_util::test_dir, DocumentUpdate, MANIFEST_FILE};
+
+/// the client waits 5 seconds for the first flood before handling anything
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+const CLIENT: NodeId = 1;
+const TEXT_SERVER: NodeId = 21;
+const MEDIA_SERVER: NodeId = 22;
+const INDEX: &str = "<html><body><p>index</p><img src=\"logo.jpg\"></body></html>";
+
+fn jpeg_base64() -> String {
+    let mut jpeg = Cursor::new(Vec::new());
+    DynamicImage::new_rgb8(4, 4)
+        .write_to(&mut jpeg, ImageFormat::Jpeg)
+        .unwrap();
+    general_purpose::STANDARD.encode(jpeg.into_inner())
+}
+
+/// `CLIENT - 11 - {12, 13} - {TEXT_SERVER, MEDIA_SERVER}`
+fn network(output_root: PathBuf, pdr: f32) -> SimulationBuilder {
+    SimulationBuilder::new(CLIENT, output_root)
+        .drone(11, pdr)
+        .drone(12, pdr)
+        .drone(13, pdr)
+        .text_server(
+            TEXT_SERVER,
+            [("index.html", INDEX), ("about.html", "<p>about</p>")],
+        )
+        .media_server(MEDIA_SERVER, [("logo.jpg", jpeg_base64())])
+        .link(CLIENT, 11)
+        .link(11, 12)
+        .link(11, 13)
+        .link(12, TEXT_SERVER)
+        .link(12, MEDIA_SERVER)
+        .link(13, TEXT_SERVER)
+        .link(13, MEDIA_SERVER)
+        .seed(7)
+}
+
+fn wait_for_servers(simulation: &Simulation) {
+    let servers = simulation.wait_ext_event(TIMEOUT, |event| match event {
+        MediaClientExtEvent::ServerDirectoryChanged(servers) if servers.len() == 2 => Some(servers),
+        _ => None,
+    });
+    let servers = servers.expect("servers not classified");
+    assert!(matches!(
+        servers[..],
+        [
+            (TEXT_SERVER, ServerType::Text),
+            (MEDIA_SERVER, ServerType::Media)
+        ]
+    ));
+}
+
+fn fetch_document(simulation: &Simulation, file_id: &str) -> DocumentUpdate {
+    simulation.send_command(MediaClientCommand::AskForFile(
+        TEXT_SERVER,
+        file_id.to_owned(),
+    ));
+    simulation
+        .wait_ext_event(TIMEOUT, |event| match event {
+            MediaClientExtEvent::DocumentUpdated(update) if update.complete => Some(update),
+            _ => None,
+        })
+        .expect("document not assembled")
+}
+
+#[test]
+fn test_document_is_fetched_through_the_network() {
+    let dir = test_dir("sim_fetch");
+    let simulation = network(dir.clone(), 0.0).start();
+    wait_for_servers(&simulation);
+
+    simulation.send_command(MediaClientCommand::AskFilesList(TEXT_SERVER));
+    let files = simulation.wait_event(TIMEOUT, |event| match event {
+        MediaClientEvent::ReceveidFileList(TEXT_SERVER, CLIENT, files) => Some(files),
+        _ => None,
+    });
+    assert_eq!(
+        files,
+        Some(vec!["about.html".to_owned(), "index.html".to_owned()])
+    );
+
+    let update = fetch_document(&simulation, "index.html");
+    assert_eq!(update.file_id, "index.html");
+    assert_eq!((update.media_received, update.media_total), (1, 1));
+    assert_eq!(fs::read_to_string(&update.path).unwrap(), INDEX);
+    assert!(update.path.with_file_name("logo.jpg").exists());
+    assert_eq!(simulation.dropped_fragments(), 0);
+
+    drop(simulation);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_document_is_fetched_through_lossy_drones() {
+    let dir = test_dir("sim_lossy");
+    let simulation = network(dir.clone(), 0.3).start();
+    wait_for_servers(&simulation);
+
+    let update = fetch_document(&simulation, "index.html");
+    assert_eq!(fs::read_to_string(&update.path).unwrap(), INDEX);
+    assert!(simulation.dropped_fragments() > 0);
+
+    drop(simulation);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_crashed_drone_is_routed_around() {
+    let dir = test_dir("sim_crash");
+    let mut simulation = network(dir.clone(), 0.0).start();
+    wait_for_servers(&simulation);
+
+    simulation.send_ext_command(MediaClientExtCommand::GetRoute(TEXT_SERVER));
+    let route = simulation
+        .wait_ext_event(TIMEOUT, |event| match event {
+            MediaClientExtEvent::Route {
+                current: Some(header),
+                ..
+            } => Some(header.hops),
+            _ => None,
+        })
+        .expect("no route to the text server");
+    let crashed = route[2];
+    simulation.crash_drone(crashed);
+
+    let update = fetch_document(&simulation, "index.html");
+    assert_eq!(fs::read_to_string(&update.path).unwrap(), INDEX);
+    let manifest = fs::read_to_string(update.path.with_file_name(MANIFEST_FILE)).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    let route = manifest["fetch"]["route"].as_array().unwrap();
+    assert_eq!(route.first(), Some(&serde_json::Value::from(CLIENT)));
+    assert_eq!(route.last(), Some(&serde_json::Value::from(TEXT_SERVER)));
+    assert!(!route.contains(&serde_json::Value::from(crashed)));
+
+    drop(simulation);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_server_content_from_dir() {
+    let dir = test_dir("sim_site");
+    fs::create_dir_all(dir.join("images")).unwrap();
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(dir.join("index.html"), INDEX).unwrap();
+    fs::write(dir.join("notes.TXT"), "notes").unwrap();
+    fs::write(dir.join("images").join("logo.jpg"), [0xff, 0xd8, 0xff]).unwrap();
+    fs::write(dir.join(".git").join("HEAD"), "ref").unwrap();
+
+    let ServerContent::Text(files) = ServerContent::text_from_dir(&dir).unwrap() else {
+        unreachable!()
+    };
+    assert_eq!(
+        files.keys().collect::<Vec<&String>>(),
+        vec!["index.html", "notes.TXT"]
+    );
+    assert_eq!(files["index.html"], INDEX);
+    let ServerContent::Media(media) = ServerContent::media_from_dir(&dir).unwrap() else {
+        unreachable!()
+    };
+    assert_eq!(
+        media.keys().collect::<Vec<&String>>(),
+        vec!["images/logo.jpg"]
+    );
+    assert_eq!(
+        media["images/logo.jpg"],
+        general_purpose::STANDARD.encode([0xff, 0xd8, 0xff])
+    );
+
+    let _ = fs::remove_dir_all(dir);
+}